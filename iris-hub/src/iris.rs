@@ -1,14 +1,82 @@
-use core::num::{NonZeroU16, NonZeroU8};
+use core::num::{NonZeroU32, NonZeroU8};
 use iris_lib::color::Color;
-use iris_lib::cue::{Cue, CHANNELS};
+use iris_lib::cue::{Cue, CueError, CHANNELS};
+use iris_lib::output::OutputStage;
+use iris_lib::protocol::{Command, MAX_MESSAGE_LEN};
+use iris_lib::sysex::BROADCAST;
+use wasm_bindgen::JsValue;
 
+use std::convert::TryInto;
+use std::fmt;
 use std::sync::Arc;
 use std::sync::Mutex;
 
-#[derive(Default)]
+/// Reasons why a call from the web interface is rejected
+#[derive(Clone, Debug, PartialEq)]
+pub enum HubError {
+    /// Changing a cue requires a launched cue
+    NoActiveCue,
+    /// There is no cue with this id
+    InvalidCueId(usize),
+    /// There is no LED with this channel
+    InvalidChannel(usize),
+    /// The string is not a color in the format `#rrggbb`
+    InvalidColor(String),
+    /// The value is not valid for a cue
+    Cue(CueError),
+    /// Gamma must be greater than 0
+    InvalidGamma(f32),
+    /// Brightness must be between 0 and 1
+    InvalidBrightness(f32),
+}
+
+impl fmt::Display for HubError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HubError::NoActiveCue => write!(f, "No cue is currently active!"),
+            HubError::InvalidCueId(id) => write!(f, "There is no cue with id {}", id),
+            HubError::InvalidChannel(channel) => write!(f, "Channel {} doesn't exist", channel),
+            HubError::InvalidColor(color) => write!(f, "{:?} is not a color like #ff0000", color),
+            HubError::Cue(error) => write!(f, "Invalid cue: {}", error),
+            HubError::InvalidGamma(value) => write!(f, "Gamma {} must be greater than 0", value),
+            HubError::InvalidBrightness(value) => {
+                write!(f, "Brightness {} must be between 0 and 1", value)
+            }
+        }
+    }
+}
+
+impl From<CueError> for HubError {
+    fn from(error: CueError) -> HubError {
+        HubError::Cue(error)
+    }
+}
+
+/// Rejected calls throw an exception with the message in JS
+impl From<HubError> for JsValue {
+    fn from(error: HubError) -> JsValue {
+        JsValue::from_str(&error.to_string())
+    }
+}
+
 pub struct Iris {
     cues: Vec<Arc<Mutex<Cue>>>,
     current: Option<Arc<Mutex<Cue>>>,
+    output: OutputStage,
+    /// Apply brightness and gamma correction like the device does.
+    /// If disabled, the linear values are shown
+    output_enabled: bool,
+}
+
+impl Default for Iris {
+    fn default() -> Iris {
+        Iris {
+            cues: Vec::new(),
+            current: None,
+            output: OutputStage::default(),
+            output_enabled: true,
+        }
+    }
 }
 
 impl Iris {
@@ -19,61 +87,173 @@ impl Iris {
     pub fn add_cue(&mut self) {
         self.cues.push(Arc::new(Mutex::new(Cue::white_breathing())));
     }
-    pub fn delete_cue(&mut self, id: usize) {
-        self.cues.remove(id);
+    pub fn delete_cue(&mut self, id: usize) -> Result<(), HubError> {
+        if id >= self.cues.len() {
+            return Err(HubError::InvalidCueId(id));
+        }
+        let cue = self.cues.remove(id);
+        // Don't keep playing a cue that can't be accessed anymore
+        if self
+            .current
+            .as_ref()
+            .is_some_and(|current| Arc::ptr_eq(current, &cue))
+        {
+            self.current = None;
+        }
+        Ok(())
     }
-    pub fn launch_cue(&mut self, id: usize) {
-        self.current = Some(self.cues[id].clone());
+    pub fn launch_cue(&mut self, id: usize) -> Result<(), HubError> {
+        let cue = self.cues.get(id).ok_or(HubError::InvalidCueId(id))?;
+        self.current = Some(cue.clone());
+        Ok(())
     }
     pub fn num_cues(&self) -> usize {
         self.cues.len()
     }
 
     pub fn current_cue_id(&self) -> Option<usize> {
+        self.current.as_ref().map(|current| {
+            self.cues
+                .iter()
+                // Not finding current in cues would be a bug
+                .position(|cue| Arc::ptr_eq(current, cue))
+                // So we unwrap and re-wrap to catch if that ever happens
+                .unwrap()
+        })
+    }
+
+    pub fn current_color(&self, time_ms: u32, channel: u8) -> Result<String, HubError> {
         match &self.current {
-            Some(current) => Some(
-                self.cues
-                    .iter()
-                    // Not finding current in cues would be a bug
-                    .position(|cue| Arc::ptr_eq(&current, &cue))
-                    // So we unwrap and re-wrap to catch if that ever happens
-                    .unwrap(),
-            ),
-            None => None,
+            Some(cue) => {
+                let color = cue.lock().unwrap().try_current_color(time_ms, channel)?;
+                if self.output_enabled {
+                    Ok(to_hex(self.output.apply(color)))
+                } else {
+                    Ok(to_hex(color))
+                }
+            }
+            None => Ok("#000".into()),
         }
     }
 
-    pub fn current_color(&self, time_ms: u32, channel: u8) -> String {
-        match &self.current {
-            Some(cue) => to_hex(cue.lock().unwrap().current_color(time_ms, channel)),
-            None => "#000".into(),
+    /// SysEx message that uploads the current cue into a slot of the device
+    pub fn upload_message(&self, slot: u8) -> Result<Vec<u8>, HubError> {
+        let current = self.current.as_ref().ok_or(HubError::NoActiveCue)?;
+        let cue = current.lock().unwrap().clone();
+        Ok(to_message(Command::UploadCue { slot, cue }))
+    }
+    /// SysEx message that starts playing the cue in a slot of the device
+    pub fn launch_message(&self, slot: u8) -> Vec<u8> {
+        to_message(Command::LaunchSlot { slot })
+    }
+
+    pub fn output_enabled(&self) -> bool {
+        self.output_enabled
+    }
+    pub fn set_output_enabled(&mut self, value: bool) {
+        self.output_enabled = value;
+    }
+    pub fn brightness(&self) -> f32 {
+        self.output.brightness().into()
+    }
+    /// # Examples
+    /// ```
+    /// use iris_hub::iris::{HubError, Iris};
+    ///
+    /// let mut iris = Iris::new();
+    /// assert_eq!(iris.set_brightness(0.5), Ok(()));
+    /// assert!(matches!(
+    ///     iris.set_brightness(f32::NAN),
+    ///     Err(HubError::InvalidBrightness(value)) if value.is_nan()
+    /// ));
+    /// assert_eq!(iris.set_brightness(1.5), Err(HubError::InvalidBrightness(1.5)));
+    /// assert_eq!(iris.brightness(), 0.5);
+    /// ```
+    pub fn set_brightness(&mut self, value: f32) -> Result<(), HubError> {
+        // Also rejects NaN, which can't be converted to a Fraction
+        if !(0.0..=1.0).contains(&value) {
+            return Err(HubError::InvalidBrightness(value));
+        }
+        self.output.set_brightness(value.into());
+        Ok(())
+    }
+    pub fn gamma(&self) -> f32 {
+        self.output.gamma().into()
+    }
+    /// # Examples
+    /// ```
+    /// use iris_hub::iris::{HubError, Iris};
+    ///
+    /// let mut iris = Iris::new();
+    /// assert_eq!(iris.set_gamma(2.5), Ok(()));
+    /// assert_eq!(iris.set_gamma(-1.0), Err(HubError::InvalidGamma(-1.0)));
+    /// assert_eq!(iris.set_gamma(0.0), Err(HubError::InvalidGamma(0.0)));
+    /// assert_eq!(iris.gamma(), 2.5);
+    /// ```
+    pub fn set_gamma(&mut self, value: f32) -> Result<(), HubError> {
+        // Also rejects NaN, which can't be converted to a Gamma
+        if value.is_nan() || value <= 0.0 {
+            return Err(HubError::InvalidGamma(value));
         }
+        self.output
+            .set_gamma(value.into())
+            .map_err(|_| HubError::InvalidGamma(value))
     }
-    /// Number of channels. Currently returns a constant value,
-    /// but this may be changed in the future
+    /// Number of channels of the simulated Iris 16
     pub fn num_channels(&self) -> u8 {
         CHANNELS
     }
 
     // Define accessors for all fields of Cue
     define_accessors!(channels;
-        channel(num: usize){ channels[num] } -> bool;
+        // Channels that don't exist are never enabled
+        channel(num: usize){ channels.get(num).copied().unwrap_or(false) } -> bool;
         // set_channel actually has the signature set_channel(num: usize, value: bool)
-        set_channel(value){ channels[num] = value });
+        set_channel(value){ *channels.get_mut(num).ok_or(HubError::InvalidChannel(num))? = value });
     define_accessors!(reverse() -> bool; set_reverse(value));
     define_accessors!(time_divisor;
         time_divisor(){time_divisor.get()} -> u8;
-        set_time_divisor(value){*time_divisor = NonZeroU8::new(value).unwrap()});
+        set_time_divisor(value){*time_divisor = NonZeroU8::new(value).ok_or(CueError::ZeroTimeDivisor)?});
     define_accessors!(duration_ms;
-        duration_ms(){duration_ms.get()} -> u16;
-        set_duration_ms(value){*duration_ms = NonZeroU16::new(value).unwrap()});
-    define_accessors!(ramp_ratio() -> f32; set_ramp_ratio(value));
+        duration_ms(){duration_ms.get()} -> u32;
+        set_duration_ms(value){*duration_ms = NonZeroU32::new(value).ok_or(CueError::ZeroDuration)?});
+    define_accessors!(ramp_ratio;
+    ramp_ratio(){f32::from(*ramp_ratio)} -> f32;
+    /// # Examples
+    /// ```
+    /// use iris_hub::iris::{HubError, Iris};
+    /// use iris_lib::cue::CueError;
+    ///
+    /// let mut iris = Iris::new();
+    /// iris.add_cue();
+    /// iris.launch_cue(0).unwrap();
+    /// assert_eq!(iris.set_ramp_ratio(0.5), Ok(()));
+    /// let invalid = Err(HubError::Cue(CueError::InvalidRampRatio));
+    /// assert_eq!(iris.set_ramp_ratio(f32::NAN), invalid);
+    /// assert_eq!(iris.set_ramp_ratio(1.5), invalid);
+    /// assert_eq!(iris.set_ramp_ratio(-0.5), invalid);
+    /// assert_eq!(iris.ramp_ratio(), 0.5);
+    /// ```
+    set_ramp_ratio(value){{
+        // Also rejects NaN, which can't be converted to a Fraction
+        if !(0.0..=1.0).contains(&value) { Err(CueError::InvalidRampRatio)? }
+        *ramp_ratio = value.into()
+    }});
     define_accessors!(start_color;
         start_color(){to_hex(*start_color)}  -> String;
-        set_start_color(value){*start_color = from_hex(value)});
+        set_start_color(value){*start_color = from_hex(value)?});
     define_accessors!(end_color;
         end_color(){to_hex(*end_color)} -> String;
-        set_end_color(value){*end_color = from_hex(value)});
+        set_end_color(value){*end_color = from_hex(value)?});
+}
+
+/// Encode a command as a SysEx message to all connected devices
+fn to_message(command: Command) -> Vec<u8> {
+    let mut message = vec![0; MAX_MESSAGE_LEN];
+    // Every command fits into MAX_MESSAGE_LEN, so this would be a bug
+    let len = command.encode_sysex(BROADCAST, &mut message).unwrap();
+    message.truncate(len);
+    message
 }
 
 /// Convert [`iris_lib::color::Color`] to a hex string
@@ -97,15 +277,20 @@ pub fn to_hex(color: Color) -> String {
 /// # Examples
 /// ```
 /// use iris_lib::color::Color;
-/// use iris_hub::iris::from_hex;
-/// assert_eq!(Color::new(0,0,0), from_hex("#000000".to_string()));
-/// assert_eq!(Color::new(127,20,255), from_hex("#7f14ff".to_string()));
-/// assert_eq!(Color::new(255,100,38), from_hex("#ff6426".to_string()));
+/// use iris_hub::iris::{from_hex, HubError};
+/// assert_eq!(Ok(Color::new(0,0,0)), from_hex("#000000".to_string()));
+/// assert_eq!(Ok(Color::new(127,20,255)), from_hex("#7f14ff".to_string()));
+/// assert_eq!(Ok(Color::new(255,100,38)), from_hex("#ff6426".to_string()));
+/// assert_eq!(Err(HubError::InvalidColor("#f00".to_string())), from_hex("#f00".to_string()));
+/// assert!(from_hex("ff6426".to_string()).is_err());
 /// ```
-pub fn from_hex(string: String) -> Color {
-    let mut str_buffer = string;
-    str_buffer.remove(0);
-    let color_vec = &hex::decode(str_buffer).unwrap();
-    let components: [u8; 3] = [color_vec[0], color_vec[1], color_vec[2]];
-    components.into()
+pub fn from_hex(string: String) -> Result<Color, HubError> {
+    let components: Option<[u8; 3]> = string
+        .strip_prefix('#')
+        .and_then(|digits| hex::decode(digits).ok())
+        .and_then(|bytes| bytes.as_slice().try_into().ok());
+    match components {
+        Some(components) => Ok(components.into()),
+        None => Err(HubError::InvalidColor(string)),
+    }
 }
@@ -2,7 +2,7 @@
 mod utils;
 pub mod iris;
 
-use iris::Iris;
+use iris::{HubError, Iris};
 use once_cell::sync::Lazy;
 use std::sync::Mutex;
 use wasm_bindgen::prelude::*;
@@ -28,24 +28,36 @@ pub fn init() {
 bind_from_iris!(add_cue());
 bind_from_iris!(num_channels() -> u8);
 bind_from_iris!(current_cue_id() -> Option<usize>);
-bind_from_iris!(delete_cue(id: usize));
-bind_from_iris!(launch_cue(id: usize));
+bind_from_iris!(delete_cue(id: usize) -> Result<(), HubError>);
+bind_from_iris!(launch_cue(id: usize) -> Result<(), HubError>);
 bind_from_iris!(num_cues() -> usize);
-bind_from_iris!(current_color(time_ms: u32, channel: u8) -> String);
+bind_from_iris!(current_color(time_ms: u32, channel: u8) -> Result<String, HubError>);
+
+// SysEx messages to send to the device via Web MIDI
+bind_from_iris!(upload_message(slot: u8) -> Result<Vec<u8>, HubError>);
+bind_from_iris!(launch_message(slot: u8) -> Vec<u8>);
+
+// Output stage
+bind_from_iris!(output_enabled() -> bool);
+bind_from_iris!(set_output_enabled(value: bool));
+bind_from_iris!(brightness() -> f32);
+bind_from_iris!(set_brightness(value: f32) -> Result<(), HubError>);
+bind_from_iris!(gamma() -> f32);
+bind_from_iris!(set_gamma(value: f32) -> Result<(), HubError>);
 
 // Accessors
 bind_from_iris!(channel(num: usize) -> bool);
-bind_from_iris!(set_channel(num: usize, value: bool));
+bind_from_iris!(set_channel(num: usize, value: bool) -> Result<(), HubError>);
 bind_from_iris!(reverse() -> bool);
-bind_from_iris!(set_reverse(value: bool));
+bind_from_iris!(set_reverse(value: bool) -> Result<(), HubError>);
 bind_from_iris!(time_divisor() -> u8);
-bind_from_iris!(set_time_divisor(value: u8));
-bind_from_iris!(duration_ms() -> u16);
-bind_from_iris!(set_duration_ms(value: u16));
+bind_from_iris!(set_time_divisor(value: u8) -> Result<(), HubError>);
+bind_from_iris!(duration_ms() -> u32);
+bind_from_iris!(set_duration_ms(value: u32) -> Result<(), HubError>);
 bind_from_iris!(ramp_ratio() -> f32);
-bind_from_iris!(set_ramp_ratio(value: f32));
+bind_from_iris!(set_ramp_ratio(value: f32) -> Result<(), HubError>);
 // Doesn't work because Color is not ABI bound
 bind_from_iris!(start_color() -> String);
-bind_from_iris!(set_start_color(value: String));
+bind_from_iris!(set_start_color(value: String) -> Result<(), HubError>);
 bind_from_iris!(end_color() -> String);
-bind_from_iris!(set_end_color(value: String));
+bind_from_iris!(set_end_color(value: String) -> Result<(), HubError>);
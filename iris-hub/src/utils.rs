@@ -23,12 +23,11 @@ macro_rules! bind_from_iris {
 
 /// Implement a getter and setter for the specified fields of the current cue
 /// `$type` is the type to convert to/from, not the one stored inside [`Cue`]
-#[macro_use]
 macro_rules! define_accessors {
     // Variant for complex case with field name, differently named
     // setter/getter statements and optional argument.
     ($field_name:ident; $getter:ident ($($arg:ident : $arg_t:ty)?){$from:stmt} -> $type:ty;
-    $setter:ident($val:ident){$to:stmt}) => {
+    $(#[$setter_attr:meta])* $setter:ident($val:ident){$to:stmt}) => {
         /// Getter for $field_name, has the same name in most cases
         /// If no cue is active, the default value will be returned
         pub fn $getter(&self $(, $arg: $arg_t)?) -> $type {
@@ -38,27 +37,25 @@ macro_rules! define_accessors {
                     $from
                 },
                 None => {
-                    let $field_name = &Cue::default().$field_name;
+                    let $field_name = &<Cue>::default().$field_name;
                     $from
                 },
             }
         }
         /// Setter for $field_name
-        /// # Panics
-        /// Panics if there is no current cue
-        pub fn $setter(&mut self, $($arg : $arg_t ,)? $val: $type) {
-            match &self.current {
-                Some(current) => {
-                    let $field_name =  &mut current.lock().unwrap().$field_name;
-                    $to
-                },
-                _ => panic!("No cue is currently active!"),
-            };
+        /// # Errors
+        /// Fails if there is no current cue or the value is invalid
+        $(#[$setter_attr])*
+        pub fn $setter(&mut self, $($arg : $arg_t ,)? $val: $type) -> Result<(), HubError> {
+            let current = self.current.as_ref().ok_or(HubError::NoActiveCue)?;
+            let $field_name = &mut current.lock().unwrap().$field_name;
+            $to
+            Ok(())
         }
     };
     // Generalized case where only the output type has to be specified
     ($field_name:ident () -> $type:ty; $setter:ident(value)) => {
-        define_accessors!($field_name; 
+        define_accessors!($field_name;
                           $field_name(){ <$type>::from(*$field_name) } -> $type;
                           $setter(value){ *$field_name = value.into() });
     };
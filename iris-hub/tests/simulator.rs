@@ -0,0 +1,59 @@
+//! Sends the messages of Iris Hub to a simulated device
+
+use iris_hub::iris::{to_hex, Iris};
+use iris_lib::protocol::{DeviceError, Parser, Response};
+use iris_lib::simulator::Simulator;
+
+/// Deliver a message to the device and return its response
+fn send(device: &mut Simulator, message: &[u8]) -> Response {
+    let mut parser = Parser::new();
+    let mut responses = Vec::new();
+    device.receive(message, |response| {
+        responses.extend(
+            response
+                .iter()
+                .filter_map(|byte| parser.push_response(*byte)),
+        )
+    });
+    assert_eq!(responses.len(), 1);
+    responses.pop().unwrap().unwrap().1
+}
+
+#[test]
+fn device_shows_the_hub_preview() {
+    let mut iris = Iris::new();
+    iris.add_cue();
+    iris.launch_cue(0).unwrap();
+    iris.set_start_color("#ff8000".to_string()).unwrap();
+    iris.set_duration_ms(2000).unwrap();
+
+    let mut device: Simulator = Simulator::new(0x00);
+    assert_eq!(
+        send(&mut device, &iris.upload_message(5).unwrap()),
+        Response::Ack
+    );
+    assert_eq!(send(&mut device, &iris.launch_message(5)), Response::Ack);
+    assert_eq!(device.launched_slot(), Some(5));
+
+    // Both apply the same output stage
+    for time_ms in (0..4000).step_by(170) {
+        let frame = device.frame();
+        for (channel, color) in frame.iter().enumerate() {
+            let preview = iris.current_color(time_ms, channel as u8).unwrap();
+            assert_eq!(to_hex(*color), preview, "{} ms", time_ms);
+        }
+        device.advance(170);
+    }
+}
+
+#[test]
+fn upload_requires_a_cue() {
+    let iris = Iris::new();
+    assert!(iris.upload_message(0).is_err());
+
+    let mut device: Simulator = Simulator::new(0x00);
+    assert_eq!(
+        send(&mut device, &iris.launch_message(0)),
+        Response::Error(DeviceError::EmptySlot(0))
+    );
+}
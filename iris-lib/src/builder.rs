@@ -0,0 +1,233 @@
+use crate::color::Color;
+use crate::cue::{Cue, CueError, Playback, RampType, CHANNELS};
+use crate::gradient::Gradient;
+use crate::phase::{PhaseMap, Symmetry};
+use crate::twinkle::Twinkle;
+use core::convert::TryFrom;
+use core::num::{NonZeroU16, NonZeroU32, NonZeroU8};
+use core::time::Duration;
+use fixed::types::U0F8;
+
+/// Builds a [`Cue`] from plain values, which are checked when calling
+/// [`CueBuilder::build`]. Fields that aren't set keep the values of
+/// [`Cue::default`], or of the Cue the builder was created from.
+/// # Examples
+/// ```
+/// use core::time::Duration;
+/// use iris_lib::builder::CueBuilder;
+/// use iris_lib::color::Color;
+/// use iris_lib::cue::{Cue, CueError, RampType};
+///
+/// let cue: Cue = CueBuilder::new()
+///     .duration(Duration::from_secs(3))
+///     .ramp_type(RampType::LinearRGB)
+///     .ramp_ratio(0.25)
+///     .colors(Color::black(), Color::new(255, 128, 0))
+///     .build()
+///     .unwrap();
+/// assert_eq!(cue.duration_ms.get(), 3000);
+///
+/// let invalid: Result<Cue, _> = CueBuilder::new().ramp_ratio(1.5).build();
+/// assert_eq!(invalid, Err(CueError::InvalidRampRatio));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CueBuilder<const N: usize = { CHANNELS as usize }> {
+    cue: Cue<N>,
+    /// The first invalid value, returned by build
+    error: Option<CueError>,
+}
+
+impl<const N: usize> Default for CueBuilder<N> {
+    fn default() -> CueBuilder<N> {
+        Cue::default().into()
+    }
+}
+
+impl<const N: usize> From<Cue<N>> for CueBuilder<N> {
+    /// Start from an existing Cue, e.g. one of the presets
+    fn from(cue: Cue<N>) -> CueBuilder<N> {
+        CueBuilder { cue, error: None }
+    }
+}
+
+impl<const N: usize> CueBuilder<N> {
+    pub fn new() -> CueBuilder<N> {
+        CueBuilder::default()
+    }
+
+    /// The Cue, or the first invalid value that was passed to the builder.
+    /// The Cue is also checked with [`Cue::validate`].
+    pub fn build(self) -> Result<Cue<N>, CueError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        self.cue.validate()?;
+        Ok(self.cue)
+    }
+
+    /// Which LEDs the Cue is shown on, starting with channel 0
+    pub fn channels(mut self, channels: [bool; N]) -> CueBuilder<N> {
+        self.cue.channels = channels;
+        self
+    }
+
+    pub fn reverse(mut self, reverse: bool) -> CueBuilder<N> {
+        self.cue.reverse = reverse;
+        self
+    }
+
+    /// Fails with [`CueError::ZeroTimeDivisor`] for 0
+    pub fn time_divisor(mut self, time_divisor: u8) -> CueBuilder<N> {
+        match NonZeroU8::new(time_divisor) {
+            Some(time_divisor) => self.cue.time_divisor = time_divisor,
+            None => self.fail(CueError::ZeroTimeDivisor),
+        }
+        self
+    }
+
+    pub fn phase_map(mut self, phase_map: PhaseMap<N>) -> CueBuilder<N> {
+        self.cue.phase_map = Some(phase_map);
+        self
+    }
+
+    pub fn symmetry(mut self, symmetry: Symmetry) -> CueBuilder<N> {
+        self.cue.symmetry = Some(symmetry);
+        self
+    }
+
+    /// Length of one period. Only whole milliseconds are used.
+    /// Fails with [`CueError::ZeroDuration`] for durations below 1 ms and
+    /// with [`CueError::DurationTooLong`] above [`u32::MAX`] ms.
+    pub fn duration(mut self, duration: Duration) -> CueBuilder<N> {
+        match u32::try_from(duration.as_millis()) {
+            Ok(duration_ms) => match NonZeroU32::new(duration_ms) {
+                Some(duration_ms) => self.cue.duration_ms = duration_ms,
+                None => self.fail(CueError::ZeroDuration),
+            },
+            Err(_) => self.fail(CueError::DurationTooLong),
+        }
+        self
+    }
+
+    pub fn ramp_type(mut self, ramp_type: RampType) -> CueBuilder<N> {
+        self.cue.ramp_type = ramp_type;
+        self
+    }
+
+    /// Fraction of the period spent on the transition from the start to the
+    /// end color. Fails with [`CueError::InvalidRampRatio`] outside of 0 to 1.
+    pub fn ramp_ratio(mut self, ramp_ratio: f32) -> CueBuilder<N> {
+        // Also rejects NaN
+        if (0.0..=1.0).contains(&ramp_ratio) {
+            self.cue.ramp_ratio = U0F8::saturating_from_num(ramp_ratio).into();
+        } else {
+            self.fail(CueError::InvalidRampRatio);
+        }
+        self
+    }
+
+    /// Start and end color
+    pub fn colors(mut self, start: Color, end: Color) -> CueBuilder<N> {
+        self.cue.start_color = start;
+        self.cue.end_color = end;
+        self
+    }
+
+    /// Transition through the stops of the gradient instead of the two colors
+    pub fn gradient(mut self, gradient: Gradient) -> CueBuilder<N> {
+        self.cue.gradient = Some(gradient);
+        self
+    }
+
+    pub fn twinkle(mut self, twinkle: Twinkle) -> CueBuilder<N> {
+        self.cue.twinkle = Some(twinkle);
+        self
+    }
+
+    pub fn playback(mut self, playback: Playback) -> CueBuilder<N> {
+        self.cue.playback = playback;
+        self
+    }
+
+    /// Play the Cue `periods` times and then hold the last frame.
+    /// Fails with [`CueError::ZeroRepeats`] for 0
+    pub fn repeat(mut self, periods: u16) -> CueBuilder<N> {
+        match NonZeroU16::new(periods) {
+            Some(periods) => self.cue.playback = Playback::Repeat(periods),
+            None => self.fail(CueError::ZeroRepeats),
+        }
+        self
+    }
+
+    /// Remember the error, unless an earlier value was invalid already
+    fn fail(&mut self, error: CueError) {
+        self.error.get_or_insert(error);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builder::*;
+
+    #[test]
+    fn matches_struct_literal() {
+        let built: Cue = CueBuilder::new()
+            .duration(Duration::from_millis(3000))
+            .ramp_type(RampType::LinearHSL {
+                hue_arc: crate::color::HueArc::Longest,
+            })
+            .ramp_ratio(1.0)
+            .colors(Color::from_hsl(0, 100, 50), Color::from_hsl(359, 100, 50))
+            .build()
+            .unwrap();
+        assert_eq!(built, Cue::rainbow());
+
+        let repeated: Cue = CueBuilder::from(Cue::rainbow())
+            .time_divisor(3)
+            .reverse(true)
+            .repeat(2)
+            .build()
+            .unwrap();
+        assert_eq!(repeated.time_divisor.get(), 3);
+        assert!(repeated.reverse);
+        assert_eq!(repeated.playback.periods(), Some(2));
+    }
+
+    #[test]
+    fn invalid_values() {
+        let builder = CueBuilder::<12>::new;
+        let duration = |duration| builder().duration(duration).build();
+        assert_eq!(duration(Duration::ZERO), Err(CueError::ZeroDuration));
+        assert_eq!(
+            duration(Duration::from_micros(999)),
+            Err(CueError::ZeroDuration)
+        );
+        assert_eq!(
+            duration(Duration::from_millis(u32::MAX as u64 + 1)),
+            Err(CueError::DurationTooLong)
+        );
+        assert!(duration(Duration::from_millis(u32::MAX as u64)).is_ok());
+
+        for ramp_ratio in [-0.1, 1.01, f32::NAN, f32::INFINITY].iter() {
+            let built = builder().ramp_ratio(*ramp_ratio).build();
+            assert_eq!(built, Err(CueError::InvalidRampRatio));
+        }
+        assert_eq!(
+            builder().time_divisor(0).build(),
+            Err(CueError::ZeroTimeDivisor)
+        );
+        assert_eq!(builder().repeat(0).build(), Err(CueError::ZeroRepeats));
+        assert_eq!(
+            builder().symmetry(Symmetry { axis: 24 }).build(),
+            Err(CueError::InvalidSymmetryAxis(24))
+        );
+
+        // The first error is kept
+        let built = builder()
+            .time_divisor(0)
+            .ramp_ratio(2.0)
+            .duration(Duration::from_secs(1))
+            .build();
+        assert_eq!(built, Err(CueError::ZeroTimeDivisor));
+    }
+}
@@ -0,0 +1,182 @@
+use crate::color::Color;
+use crate::cue::CHANNELS;
+use fixed::types::U8F8;
+use serde::{Deserialize, Serialize};
+
+/// Factors for red, green and blue, where 255 keeps the value unchanged
+/// and 0 turns the LED off. LEDs can only be dimmed, not boosted.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct RgbGains {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl Default for RgbGains {
+    /// Unchanged colors
+    fn default() -> RgbGains {
+        RgbGains::new(u8::MAX, u8::MAX, u8::MAX)
+    }
+}
+
+impl RgbGains {
+    pub fn new(red: u8, green: u8, blue: u8) -> RgbGains {
+        RgbGains { red, green, blue }
+    }
+
+    /// Gains that turn the measured color of full white into a neutral white
+    /// by dimming the two stronger colors to the level of the weakest one.
+    /// The measurement needs to be linear, e.g. the raw values of a sensor.
+    /// # Examples
+    /// ```
+    /// use iris_lib::calibration::RgbGains;
+    /// use iris_lib::color::Color;
+    ///
+    /// // This LED has a strong blue tint
+    /// let gains = RgbGains::from_measured_white(Color::new(200, 220, 250));
+    /// assert_eq!(gains, RgbGains::new(255, 232, 204));
+    /// ```
+    pub fn from_measured_white(measured: Color) -> RgbGains {
+        let [red, green, blue]: [u8; 3] = measured.into();
+        let weakest = red.min(green).min(blue) as u32;
+        let gain = |value: u8| match value {
+            0 => u8::MAX,
+            _ => ((weakest * u8::MAX as u32 + value as u32 / 2) / value as u32) as u8,
+        };
+        RgbGains::new(gain(red), gain(green), gain(blue))
+    }
+
+    /// Combine two gains, so applying the result is the same as applying both
+    fn combine(&self, other: &RgbGains) -> [u16; 3] {
+        [
+            self.red as u16 * other.red as u16,
+            self.green as u16 * other.green as u16,
+            self.blue as u16 * other.blue as u16,
+        ]
+    }
+}
+
+/// Corrects the differences between the LEDs of a single device, so the same
+/// color looks the same on every LED and white looks neutral. Applied to the
+/// values that are sent to the LEDs, so after the
+/// [`OutputStage`](crate::output::OutputStage).
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct CalibrationProfile<const N: usize = { CHANNELS as usize }> {
+    /// Gains for each LED, starting with channel 0
+    #[serde(with = "crate::serde_array")]
+    pub channels: [RgbGains; N],
+    /// Gains applied to all LEDs, to adjust the color of white
+    pub white_point: RgbGains,
+}
+
+impl<const N: usize> Default for CalibrationProfile<N> {
+    /// Doesn't change any colors
+    fn default() -> CalibrationProfile<N> {
+        CalibrationProfile {
+            channels: [RgbGains::default(); N],
+            white_point: RgbGains::default(),
+        }
+    }
+}
+
+impl<const N: usize> CalibrationProfile<N> {
+    pub fn new() -> CalibrationProfile<N> {
+        CalibrationProfile::default()
+    }
+
+    /// Correct the color of a single LED
+    /// # Examples
+    /// ```
+    /// use iris_lib::calibration::{CalibrationProfile, RgbGains};
+    /// use iris_lib::color::Color;
+    ///
+    /// let mut profile: CalibrationProfile = CalibrationProfile::new();
+    /// profile.channels[3] = RgbGains::new(255, 128, 255);
+    /// profile.white_point = RgbGains::new(255, 255, 128);
+    /// assert_eq!(profile.apply(3, Color::white()), Color::new(255, 128, 128));
+    /// assert_eq!(profile.apply(4, Color::white()), Color::new(255, 255, 128));
+    /// ```
+    pub fn apply(&self, channel: u8, color: Color) -> Color {
+        let gains = self.channels[channel as usize].combine(&self.white_point);
+        let [red, green, blue]: [u8; 3] = color.into();
+        Color::new(
+            scale(red as u16, gains[0]) as u8,
+            scale(green as u16, gains[1]) as u8,
+            scale(blue as u16, gains[2]) as u8,
+        )
+    }
+
+    /// Like [`CalibrationProfile::apply`], but keeps the fractional bits of a
+    /// color from [`OutputStage::apply_precise`](crate::output::OutputStage::apply_precise)
+    pub fn apply_precise(&self, channel: u8, color: [U8F8; 3]) -> [U8F8; 3] {
+        let gains = self.channels[channel as usize].combine(&self.white_point);
+        let scale = |value: U8F8, gain: u16| U8F8::from_bits(scale(value.to_bits(), gain));
+        [
+            scale(color[0], gains[0]),
+            scale(color[1], gains[1]),
+            scale(color[2], gains[2]),
+        ]
+    }
+
+    /// Correct all colors of a rendered frame in place, starting with channel 0
+    pub fn apply_frame(&self, frame: &mut [Color]) {
+        for (channel, color) in (0..N as u8).zip(frame.iter_mut()) {
+            *color = self.apply(channel, *color);
+        }
+    }
+}
+
+/// value * gain / (255 * 255), rounded
+fn scale(value: u16, gain: u16) -> u16 {
+    const MAX_GAIN: u64 = u8::MAX as u64 * u8::MAX as u64;
+    ((value as u64 * gain as u64 + MAX_GAIN / 2) / MAX_GAIN) as u16
+}
+
+#[cfg(test)]
+mod test {
+    use crate::calibration::*;
+
+    #[test]
+    fn default_is_unchanged() {
+        let profile: CalibrationProfile = CalibrationProfile::new();
+        for value in 0..=u8::MAX {
+            let color = Color::new(value, u8::MAX - value, value / 2);
+            for channel in 0..CHANNELS {
+                assert_eq!(profile.apply(channel, color), color);
+            }
+        }
+    }
+
+    #[test]
+    fn apply_frame() {
+        let mut profile = CalibrationProfile::<4>::new();
+        profile.channels[1] = RgbGains::new(0, 255, 255);
+        profile.white_point = RgbGains::new(255, 255, 0);
+
+        let mut frame = [Color::white(); 5];
+        profile.apply_frame(&mut frame);
+        assert_eq!(
+            frame,
+            [
+                Color::new(255, 255, 0),
+                Color::new(0, 255, 0),
+                Color::new(255, 255, 0),
+                Color::new(255, 255, 0),
+                // Only N LEDs are calibrated
+                Color::white(),
+            ]
+        );
+    }
+
+    #[test]
+    fn measured_white() {
+        assert_eq!(
+            RgbGains::from_measured_white(Color::white()),
+            RgbGains::default()
+        );
+        assert_eq!(
+            RgbGains::from_measured_white(Color::new(0, 100, 50)),
+            RgbGains::new(255, 0, 0)
+        );
+    }
+}
@@ -1,5 +1,7 @@
+use crate::oklab;
 use fixed::types::U0F8;
-pub use palette::{Hsl, Mix, Srgb};
+#[cfg(feature = "float")]
+pub use palette::{Hsl, Hsv, LinSrgb, Srgb};
 use serde::{Deserialize, Serialize};
 
 /// Describes an RGB color. This is the format used for storing colors
@@ -23,9 +25,22 @@ impl Color {
         [255, 255, 255].into()
     }
 
+    /// Create a color from hue in degrees and saturation and lightness in percent
+    /// # Examples
+    /// ```
+    /// use iris_lib::color::Color;
+    ///
+    /// assert_eq!(Color::from_hsl(0, 100, 50), Color::new(255, 0, 0));
+    /// assert_eq!(Color::from_hsl(240, 100, 25), Color::new(0, 0, 128));
+    /// assert_eq!(Color::from_hsl(120, 0, 100), Color::white());
+    /// ```
     pub fn from_hsl(h: u16, s: u8, l: u8) -> Color {
-        let float_hsl = Hsl::new(h as f32, s as f32, l as f32);
-        float_hsl.into()
+        Hsl16 {
+            hue: hue_from_degrees(h),
+            saturation: div_round(s.min(100) as u32 * u16::MAX as u32, 100) as u16,
+            lightness: div_round(l.min(100) as u32 * u16::MAX as u32, 100) as u16,
+        }
+        .into()
     }
 
     pub fn linear_mix_rgb(&self, other: &Color, factor: U0F8) -> Color {
@@ -36,18 +51,392 @@ impl Color {
         }
     }
 
-    pub fn linear_mix_hsl(self, other: Color, factor: U0F8, _wrap_hue: bool) -> Color {
-        // TODO: Interpolate values manually and implement wrap_hue
-        // Maybe as a separate function, mix likely does something else than linear interpolation
-        let self_hsl: Hsl = self.into();
-        let other_hsl: Hsl = other.into();
+    /// Interpolate hue, saturation and lightness linearly. The hue moves
+    /// along the color wheel in the direction specified by `hue_arc`.
+    /// If one of the colors is a shade of grey, its hue is meaningless, so
+    /// the hue of the other color is kept. For black and white, the same goes
+    /// for the saturation. This way, fading from black to a color only
+    /// changes the lightness.
+    pub fn linear_mix_hsl(self, other: Color, factor: U0F8, hue_arc: HueArc) -> Color {
+        let self_hsl: Hsl16 = self.into();
+        let other_hsl: Hsl16 = other.into();
+        let (self_hsl, other_hsl) = (
+            self_hsl.replace_undefined(other_hsl),
+            other_hsl.replace_undefined(self_hsl),
+        );
+
+        Hsl16 {
+            hue: mix_hue(self_hsl.hue, other_hsl.hue, factor, hue_arc),
+            saturation: interpolate_u16(self_hsl.saturation, other_hsl.saturation, factor),
+            lightness: interpolate_u16(self_hsl.lightness, other_hsl.lightness, factor),
+        }
+        .into()
+    }
+
+    /// Interpolate in the OKLab color space. Unlike mixing in RGB, the colors
+    /// in between keep an even perceived brightness, see [`Oklab`](crate::oklab::Oklab).
+    /// # Examples
+    /// ```
+    /// use iris_lib::color::Color;
+    /// use fixed::types::U0F8;
+    /// use fixed_macro::types::U0F8;
+    ///
+    /// let red = Color::new(255, 0, 0);
+    /// let blue = Color::new(0, 0, 255);
+    /// assert_eq!(red.linear_mix_oklab(blue, U0F8!(0.5)), Color::new(140, 83, 163));
+    /// // Mixing in RGB is much darker in between
+    /// assert_eq!(red.linear_mix_rgb(&blue, U0F8!(0.5)), Color::new(127, 0, 128));
+    /// ```
+    pub fn linear_mix_oklab(self, other: Color, factor: U0F8) -> Color {
+        OklabPair::new(self, other).mix(factor)
+    }
+
+    /// Interpolate lightness, chroma and hue in the OKLCh color space, which
+    /// is OKLab in polar coordinates. The hue moves along the color wheel in
+    /// the direction specified by `hue_arc`. If one of the colors is a shade
+    /// of grey, the hue of the other color is kept, like in [`Color::linear_mix_hsl`].
+    pub fn linear_mix_oklch(self, other: Color, factor: U0F8, hue_arc: HueArc) -> Color {
+        OklabPair::new(self, other).mix_polar(factor, hue_arc)
+    }
+
+    /// Combine `top` with this color below it, see [`BlendMode`].
+    /// The result is mixed with this color according to `opacity`,
+    /// where [`U0F8::MAX`] is fully opaque.
+    /// # Examples
+    /// ```
+    /// use iris_lib::color::{BlendMode, Color};
+    /// use fixed::types::U0F8;
+    /// use fixed_macro::types::U0F8;
+    ///
+    /// let base = Color::new(200, 100, 0);
+    /// let pulse = Color::new(100, 100, 100);
+    /// assert_eq!(base.blend(pulse, BlendMode::Add, U0F8::MAX), Color::new(255, 200, 100));
+    /// assert_eq!(base.blend(pulse, BlendMode::Add, U0F8!(0.5)), Color::new(227, 150, 50));
+    /// assert_eq!(base.blend(pulse, BlendMode::Lighten, U0F8::MAX), Color::new(200, 100, 100));
+    /// ```
+    pub fn blend(self, top: Color, mode: BlendMode, opacity: U0F8) -> Color {
+        let blend_channel = |below: u8, top: u8| match mode {
+            BlendMode::Replace | BlendMode::Alpha => top,
+            BlendMode::Add => below.saturating_add(top),
+            BlendMode::Multiply => multiply(below, top),
+            BlendMode::Screen => u8::MAX - multiply(u8::MAX - below, u8::MAX - top),
+            BlendMode::Lighten => below.max(top),
+        };
+        let blended = Color {
+            red: blend_channel(self.red, top.red),
+            green: blend_channel(self.green, top.green),
+            blue: blend_channel(self.blue, top.blue),
+        };
+        match mode {
+            BlendMode::Replace => blended,
+            _ => self.linear_mix_rgb(&blended, opacity),
+        }
+    }
+}
+
+/// How a color is combined with the color below it, e.g. when layering
+/// Cues in a [`Schedule`](crate::schedule::Schedule)
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// Only the top color is visible, regardless of the opacity
+    #[default]
+    Replace,
+    /// Mix the top color with the one below according to the opacity
+    Alpha,
+    /// Add both colors, which can only get brighter
+    Add,
+    /// Multiply both colors, which can only get darker. White keeps the color below
+    Multiply,
+    /// Inverse of multiplying the inverted colors, which can only get brighter.
+    /// Black keeps the color below
+    Screen,
+    /// Take the brighter value of each of red, green and blue
+    Lighten,
+}
+
+/// Describes a color by hue, saturation and lightness using fixed-point numbers.
+/// This uses the common definition (as in CSS) on top of the stored RGB values.
+/// Converting from [`Color`] and back is lossless.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Hsl16 {
+    /// Position on the color wheel, where a full turn of 360° is 65536
+    pub hue: u16,
+    /// Saturation, where 65535 is fully saturated
+    pub saturation: u16,
+    /// Lightness, where 0 is black and 65535 is white
+    pub lightness: u16,
+}
+
+/// Describes a color by hue, saturation and value using fixed-point numbers.
+/// Converting from [`Color`] and back is lossless.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Hsv16 {
+    /// Position on the color wheel, where a full turn of 360° is 65536
+    pub hue: u16,
+    /// Saturation, where 65535 is fully saturated
+    pub saturation: u16,
+    /// Value, where 0 is black and 65535 is the brightest color of this hue
+    pub value: u16,
+}
+
+impl Hsl16 {
+    /// Take hue and saturation from `other` if they are meaningless in `self`
+    fn replace_undefined(mut self, other: Hsl16) -> Hsl16 {
+        if self.saturation == 0 {
+            self.hue = other.hue;
+        }
+        if self.lightness == 0 || self.lightness == u16::MAX {
+            self.saturation = other.saturation;
+        }
+        self
+    }
+}
+
+impl From<Color> for Hsl16 {
+    fn from(color: Color) -> Hsl16 {
+        let (max, min) = max_min(color);
+        let sum = max + min;
+        let chroma = max - min;
+        // Actual formula: chroma / (1 - |2 * lightness - 1|)
+        let saturation = match sum {
+            0 | 510 => 0,
+            _ if sum <= 255 => div_round(chroma * u16::MAX as u32, sum),
+            _ => div_round(chroma * u16::MAX as u32, 510 - sum),
+        };
+        Hsl16 {
+            hue: hue(color),
+            saturation: saturation as u16,
+            lightness: div_round(sum * u16::MAX as u32, 510) as u16,
+        }
+    }
+}
 
-        let new_hsl = self_hsl.mix(&other_hsl, factor.to_num());
+impl From<Hsl16> for Color {
+    fn from(hsl: Hsl16) -> Color {
+        let lightness = hsl.lightness as u32;
+        // Actual formula: (1 - |2 * lightness - 1|) * saturation
+        let spread = if lightness <= u16::MAX as u32 / 2 {
+            2 * lightness
+        } else {
+            2 * (u16::MAX as u32 - lightness)
+        };
+        let chroma = div_round(spread * hsl.saturation as u32, u16::MAX as u32);
+        from_chroma(hsl.hue, chroma, lightness - chroma / 2)
+    }
+}
 
-        new_hsl.into()
+impl From<Color> for Hsv16 {
+    fn from(color: Color) -> Hsv16 {
+        let (max, min) = max_min(color);
+        let saturation = match max {
+            0 => 0,
+            _ => div_round((max - min) * u16::MAX as u32, max),
+        };
+        Hsv16 {
+            hue: hue(color),
+            saturation: saturation as u16,
+            value: div_round(max * u16::MAX as u32, 255) as u16,
+        }
     }
 }
 
+impl From<Hsv16> for Color {
+    fn from(hsv: Hsv16) -> Color {
+        let value = hsv.value as u32;
+        let chroma = div_round(value * hsv.saturation as u32, u16::MAX as u32);
+        from_chroma(hsv.hue, chroma, value - chroma)
+    }
+}
+
+/// Largest and smallest component of a color
+fn max_min(color: Color) -> (u32, u32) {
+    let Color { red, green, blue } = color;
+    (
+        red.max(green).max(blue) as u32,
+        red.min(green).min(blue) as u32,
+    )
+}
+
+/// Hue of a color, where a full turn is 65536. Shades of grey have a hue of 0
+fn hue(color: Color) -> u16 {
+    let (red, green, blue) = (color.red as i32, color.green as i32, color.blue as i32);
+    let (max, min) = max_min(color);
+    let chroma = (max - min) as i32;
+    if chroma == 0 {
+        return 0;
+    }
+
+    // Position on the color wheel in sixths of a turn, scaled by chroma.
+    // Each of the three cases covers two sixths around its primary color.
+    let sixths = if max as i32 == red {
+        green - blue
+    } else if max as i32 == green {
+        blue - red + 2 * chroma
+    } else {
+        red - green + 4 * chroma
+    };
+    let sixths = if sixths < 0 {
+        sixths + 6 * chroma
+    } else {
+        sixths
+    } as u32;
+
+    // sixths ≤ 6 * 255, so this can't overflow. A full turn wraps to 0
+    (div_round(sixths << 16, 6 * chroma as u32) & u16::MAX as u32) as u16
+}
+
+/// Convert hue, chroma and the offset shared by all components to a color.
+/// `chroma + offset` must not be larger than u16::MAX
+fn from_chroma(hue: u16, chroma: u32, offset: u32) -> Color {
+    // Split hue into the sixth of the color wheel and the position inside it
+    let position = hue as u32 * 6;
+    let sector = position >> 16;
+    let fraction = position & u16::MAX as u32;
+
+    // chroma and fraction are both ≤ u16::MAX, so this can't overflow
+    let rising = (chroma * fraction + (1 << 15)) >> 16;
+    let falling = chroma - rising;
+    let (red, green, blue) = match sector {
+        0 => (chroma, rising, 0),
+        1 => (falling, chroma, 0),
+        2 => (0, chroma, rising),
+        3 => (0, falling, chroma),
+        4 => (rising, 0, chroma),
+        _ => (chroma, 0, falling),
+    };
+
+    let to_u8 = |component: u32| div_round((component + offset) * 255, u16::MAX as u32) as u8;
+    Color::new(to_u8(red), to_u8(green), to_u8(blue))
+}
+
+/// Convert degrees to a hue where a full turn is 65536
+fn hue_from_degrees(degrees: u16) -> u16 {
+    let degrees = (degrees % 360) as u32;
+    div_round(degrees << 16, 360) as u16
+}
+
+/// Two colors converted to OKLab once, so they can be mixed many times, e.g.
+/// for every LED of a frame, without converting them again
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OklabPair {
+    start: Color,
+    end: Color,
+    start_lab: [i64; 3],
+    end_lab: [i64; 3],
+    /// Chroma and hue of both colors. Greys take the hue of the other color
+    start_polar: (i64, u16),
+    end_polar: (i64, u16),
+}
+
+impl OklabPair {
+    pub fn new(start: Color, end: Color) -> OklabPair {
+        let start_lab = oklab::to_lab(start);
+        let end_lab = oklab::to_lab(end);
+        let (start_chroma, start_hue) = oklab::to_polar([start_lab[1], start_lab[2]]);
+        let (end_chroma, end_hue) = oklab::to_polar([end_lab[1], end_lab[2]]);
+        let is_grey = |chroma: i64| chroma < oklab::GREY_CHROMA;
+        let (start_hue, end_hue) = match (is_grey(start_chroma), is_grey(end_chroma)) {
+            (true, false) => (end_hue, end_hue),
+            (false, true) => (start_hue, start_hue),
+            _ => (start_hue, end_hue),
+        };
+        OklabPair {
+            start,
+            end,
+            start_lab,
+            end_lab,
+            start_polar: (start_chroma, start_hue),
+            end_polar: (end_chroma, end_hue),
+        }
+    }
+
+    /// See [`Color::linear_mix_oklab`]
+    pub fn mix(&self, factor: U0F8) -> Color {
+        let mix =
+            |index: usize| interpolate_i64(self.start_lab[index], self.end_lab[index], factor);
+        oklab::from_lab([mix(0), mix(1), mix(2)])
+    }
+
+    /// See [`Color::linear_mix_oklch`]
+    pub fn mix_polar(&self, factor: U0F8, hue_arc: HueArc) -> Color {
+        // The hue only has 16 bits, so converting back can be off by one
+        if factor == U0F8::MIN {
+            return self.start;
+        } else if factor == U0F8::MAX {
+            return self.end;
+        }
+
+        let ((start_chroma, start_hue), (end_chroma, end_hue)) = (self.start_polar, self.end_polar);
+        let chroma = interpolate_i64(start_chroma, end_chroma, factor);
+        let hue = mix_hue(start_hue, end_hue, factor, hue_arc);
+        let [a, b] = oklab::from_polar(chroma, hue);
+        let lightness = interpolate_i64(self.start_lab[0], self.end_lab[0], factor);
+        oklab::from_lab([lightness, a, b])
+    }
+}
+
+/// Integer division, rounded to the nearest integer
+fn div_round(dividend: u32, divisor: u32) -> u32 {
+    (dividend + divisor / 2) / divisor
+}
+
+/// The direction in which the hue moves along the color wheel when mixing in HSL.
+/// If both ways are equally long, the hue increases.
+/// If both hues are equal, the hue stays the same.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum HueArc {
+    /// Take the shorter way around the color wheel, crossing 0°/360° if necessary.
+    /// For example, this transitions from yellow to pink through red,
+    /// instead of through green, cyan and blue.
+    Shortest,
+    /// Take the longer way around the color wheel. For example, this transitions
+    /// from red (0°) to pink (330°) through all colors of the rainbow.
+    Longest,
+}
+
+/// Interpolate between two hues along the specified arc, where a full turn is 65536
+fn mix_hue(start: u16, end: u16, factor: U0F8, hue_arc: HueArc) -> u16 {
+    const TURN: i32 = 1 << 16;
+    // Difference along the shorter arc, -TURN/2 ≤ shortest < TURN/2
+    let shortest = end.wrapping_sub(start) as i16 as i32;
+    let delta = match hue_arc {
+        // Both ways are equally long, so make sure the hue increases
+        _ if shortest == -TURN / 2 => TURN / 2,
+        HueArc::Shortest => shortest,
+        // Equal hues don't move at all, even when taking the longest way
+        HueArc::Longest if shortest == 0 => 0,
+        HueArc::Longest if shortest > 0 => shortest - TURN,
+        HueArc::Longest => shortest + TURN,
+    };
+
+    // Use the same scale as `interpolate`, so U0F8::MAX reaches `end` exactly.
+    // |delta| ≤ TURN, so this can't overflow
+    let scaled = delta * factor.to_bits() as i32;
+    let offset = (scaled + scaled.signum() * (u8::MAX as i32 / 2)) / u8::MAX as i32;
+    // Truncating to u16 wraps around the color wheel
+    start.wrapping_add(offset as u16)
+}
+
+/// Product of two numbers between 0 and 1, where u8::MAX is 1
+fn multiply(a: u8, b: u8) -> u8 {
+    div_round(a as u32 * b as u32, u8::MAX as u32) as u8
+}
+
+/// Like [`interpolate`], but for i64 and rounded to the nearest integer
+fn interpolate_i64(start: i64, end: i64, factor: U0F8) -> i64 {
+    let scaled = (end - start) * factor.to_bits() as i64;
+    let offset = (scaled + scaled.signum() * (u8::MAX as i64 / 2)) / u8::MAX as i64;
+    start + offset
+}
+
+/// Like [`interpolate`], but for u16 and rounded to the nearest integer
+fn interpolate_u16(start: u16, end: u16, factor: U0F8) -> u16 {
+    let delta = end as i32 - start as i32;
+    let scaled = delta * factor.to_bits() as i32;
+    let offset = (scaled + scaled.signum() * (u8::MAX as i32 / 2)) / u8::MAX as i32;
+    (start as i32 + offset) as u16
+}
+
 /// Interpolate between two numbers using a fixed-point factor between 0 and 1
 /// # Examples
 /// ```
@@ -85,23 +474,50 @@ pub fn interpolate(start: u8, end: u8, factor: U0F8) -> u8 {
     }
 }
 
+// palette's Hsl is defined on top of linear RGB. We want the common definition
+// (as used in CSS), which works directly on the gamma-encoded values we store.
+// So the encoded components are passed to palette as if they were linear.
+
+#[cfg(feature = "float")]
 impl From<Hsl> for Color {
     fn from(hsl: Hsl) -> Color {
-        let float_rgb: Srgb = hsl.into();
-        let u8_rgb: Srgb<u8> = float_rgb.into_format();
-        u8_rgb.into()
+        let float_rgb: LinSrgb = hsl.into();
+        let u8_rgb: LinSrgb<u8> = float_rgb.into_format();
+        let (red, green, blue) = u8_rgb.into_components();
+        Color { red, green, blue }
     }
 }
 
+#[cfg(feature = "float")]
 impl From<Color> for Hsl {
     fn from(color: Color) -> Hsl {
-        let u8_rgb: Srgb<u8> = color.into();
-        let float_rgb: Srgb = u8_rgb.into_format();
+        let u8_rgb = LinSrgb::<u8>::new(color.red, color.green, color.blue);
+        let float_rgb: LinSrgb = u8_rgb.into_format();
+        float_rgb.into()
+    }
+}
+
+#[cfg(feature = "float")]
+impl From<Hsv> for Color {
+    fn from(hsv: Hsv) -> Color {
+        let float_rgb: LinSrgb = hsv.into();
+        let u8_rgb: LinSrgb<u8> = float_rgb.into_format();
+        let (red, green, blue) = u8_rgb.into_components();
+        Color { red, green, blue }
+    }
+}
+
+#[cfg(feature = "float")]
+impl From<Color> for Hsv {
+    fn from(color: Color) -> Hsv {
+        let u8_rgb = LinSrgb::<u8>::new(color.red, color.green, color.blue);
+        let float_rgb: LinSrgb = u8_rgb.into_format();
         float_rgb.into()
     }
 }
 
 /// Allow conversion to palette's integer RGB type
+#[cfg(feature = "float")]
 impl From<Color> for Srgb<u8> {
     fn from(color: Color) -> Srgb<u8> {
         Srgb::<u8>::from_components((color.red, color.green, color.blue))
@@ -109,6 +525,7 @@ impl From<Color> for Srgb<u8> {
 }
 
 /// Allow conversion from palette's integer RGB type
+#[cfg(feature = "float")]
 impl From<Srgb<u8>> for Color {
     fn from(u8_rgb: Srgb<u8>) -> Color {
         let (red, green, blue) = u8_rgb.into_components();
@@ -152,4 +569,341 @@ mod test {
         assert_eq!(interpolate(0, 255, U0F8!(0.5)), 128);
         assert_eq!(interpolate(0, 255, U0F8!(0.25)), 64);
     }
+
+    fn degrees(degrees: u16) -> u16 {
+        hue_from_degrees(degrees)
+    }
+
+    fn assert_hue(actual: u16, expected_degrees: u16) {
+        let difference = actual.wrapping_sub(degrees(expected_degrees)) as i16;
+        assert!(
+            difference.abs() <= 1,
+            "{} != {}°",
+            actual as f32 * 360.0 / 65536.0,
+            expected_degrees
+        );
+    }
+
+    fn assert_color_hue(color: Color, expected_degrees: u16) {
+        let hsl: Hsl16 = color.into();
+        // Hue can only be calculated accurately enough for bright colors
+        let difference = hsl.hue.wrapping_sub(degrees(expected_degrees)) as i16;
+        assert!(difference.abs() < 100, "{:?} != {}°", hsl, expected_degrees);
+    }
+
+    #[test]
+    fn test_mix_hue() {
+        let third = U0F8::from_bits(85);
+        let fifth = U0F8::from_bits(51);
+        let mix =
+            |start, end, factor, hue_arc| mix_hue(degrees(start), degrees(end), factor, hue_arc);
+
+        // Red (0°) to blue (240°)
+        assert_hue(mix(0, 240, fifth, HueArc::Shortest), 336);
+        assert_hue(mix(0, 240, fifth, HueArc::Longest), 48);
+        // Blue (240°) to red (0°)
+        assert_hue(mix(240, 0, fifth, HueArc::Shortest), 264);
+        assert_hue(mix(240, 0, fifth, HueArc::Longest), 192);
+        // Yellow (60°) to pink (330°)
+        assert_hue(mix(60, 330, third, HueArc::Shortest), 30);
+        assert_hue(mix(60, 330, third, HueArc::Longest), 150);
+        // Opposite hues always increase
+        assert_hue(mix(0, 180, third, HueArc::Shortest), 60);
+        assert_hue(mix(180, 0, third, HueArc::Shortest), 240);
+        assert_hue(mix(180, 0, third, HueArc::Longest), 240);
+        // End hue is reached exactly
+        assert_eq!(mix(60, 330, U0F8::MAX, HueArc::Shortest), degrees(330));
+        assert_eq!(mix(0, 359, U0F8::MAX, HueArc::Longest), degrees(359));
+        // Equal hues stay equal
+        assert_eq!(mix(120, 120, third, HueArc::Longest), degrees(120));
+    }
+
+    #[test]
+    fn test_linear_mix_hsl() {
+        let red = Color::new(255, 0, 0);
+        let blue = Color::new(0, 0, 255);
+        let yellow = Color::new(255, 255, 0);
+        let magenta = Color::new(255, 0, 255);
+
+        // Red to blue, a third of the way
+        let shortest = red.linear_mix_hsl(blue, U0F8::from_bits(85), HueArc::Shortest);
+        let longest = red.linear_mix_hsl(blue, U0F8::from_bits(85), HueArc::Longest);
+        assert_color_hue(shortest, 320);
+        assert_color_hue(longest, 80);
+        assert_eq!(shortest, Color::new(255, 0, 170));
+        assert_eq!(longest, Color::new(170, 255, 0));
+
+        // Yellow to magenta, a fifth of the way
+        let shortest = yellow.linear_mix_hsl(magenta, U0F8::from_bits(51), HueArc::Shortest);
+        let longest = yellow.linear_mix_hsl(magenta, U0F8::from_bits(51), HueArc::Longest);
+        assert_color_hue(shortest, 36);
+        assert_color_hue(longest, 108);
+        assert_eq!(shortest, Color::new(255, 153, 0));
+        assert_eq!(longest, Color::new(51, 255, 0));
+
+        // Start and end are reached exactly
+        for arc in [HueArc::Shortest, HueArc::Longest].iter() {
+            assert_eq!(red.linear_mix_hsl(blue, U0F8::MIN, *arc), red);
+            assert_eq!(red.linear_mix_hsl(blue, U0F8::MAX, *arc), blue);
+        }
+    }
+
+    #[test]
+    fn test_linear_mix_hsl_grey() {
+        // Hue of grey is ignored, so only lightness changes
+        let blue = Color::new(0, 0, 255);
+        let half = Color::black().linear_mix_hsl(blue, U0F8::from_bits(51), HueArc::Longest);
+        assert_color_hue(half, 240);
+        assert_eq!(half, Color::new(0, 0, 51));
+        let half = blue.linear_mix_hsl(Color::white(), U0F8::from_bits(51), HueArc::Longest);
+        assert_color_hue(half, 240);
+    }
+
+    /// Some colors from all regions of the RGB cube
+    #[cfg(feature = "float")]
+    fn sample_colors() -> impl Iterator<Item = Color> {
+        static LEVELS: [u8; 9] = [0, 1, 37, 64, 127, 128, 200, 254, 255];
+        LEVELS.iter().flat_map(|&red| {
+            LEVELS.iter().flat_map(move |&green| {
+                LEVELS.iter().map(move |&blue| Color::new(red, green, blue))
+            })
+        })
+    }
+
+    #[test]
+    fn lossless_round_trip() {
+        for red in (0..=255).step_by(3) {
+            for green in (0..=255).step_by(5) {
+                for blue in (0..=255).step_by(7) {
+                    let color = Color::new(red, green, blue);
+                    assert_eq!(Color::from(Hsl16::from(color)), color);
+                    assert_eq!(Color::from(Hsv16::from(color)), color);
+                }
+            }
+        }
+    }
+
+    /// The previous implementation using palette's floating point types,
+    /// used as a reference for the fixed-point implementation
+    #[cfg(feature = "float")]
+    mod float {
+        use crate::color::*;
+
+        fn mix_hue(start: f32, end: f32, factor: f32, hue_arc: HueArc) -> f32 {
+            let delta = end - start;
+            let is_shortest = delta.abs() < 180.0;
+            let delta = match (hue_arc, is_shortest) {
+                _ if delta == 0.0 => 0.0,
+                _ if delta.abs() == 180.0 => 180.0,
+                (HueArc::Shortest, true) | (HueArc::Longest, false) => delta,
+                _ if delta > 0.0 => delta - 360.0,
+                _ => delta + 360.0,
+            };
+            (start + delta * factor + 360.0) % 360.0
+        }
+
+        fn replace_undefined(mut hsl: Hsl, other: Hsl) -> Hsl {
+            if hsl.saturation == 0.0 {
+                hsl.hue = other.hue;
+            }
+            if hsl.lightness == 0.0 || hsl.lightness == 1.0 {
+                hsl.saturation = other.saturation;
+            }
+            hsl
+        }
+
+        pub fn linear_mix_hsl(start: Color, end: Color, factor: U0F8, hue_arc: HueArc) -> Color {
+            let start_hsl = replace_undefined(start.into(), end.into());
+            let end_hsl = replace_undefined(end.into(), start.into());
+            let factor = factor.to_bits() as f32 / u8::MAX as f32;
+            let mix = |start: f32, end: f32| start + (end - start) * factor;
+            Hsl::new(
+                mix_hue(
+                    start_hsl.hue.to_positive_degrees(),
+                    end_hsl.hue.to_positive_degrees(),
+                    factor,
+                    hue_arc,
+                ),
+                mix(start_hsl.saturation, end_hsl.saturation),
+                mix(start_hsl.lightness, end_hsl.lightness),
+            )
+            .into()
+        }
+    }
+
+    #[cfg(feature = "float")]
+    fn assert_within_one(actual: Color, expected: Color) {
+        let actual: [u8; 3] = actual.into();
+        let expected: [u8; 3] = expected.into();
+        assert!(
+            actual
+                .iter()
+                .zip(expected.iter())
+                .all(|(a, e)| (*a as i16 - *e as i16).abs() <= 1),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "float")]
+    fn conversions_match_float() {
+        for color in sample_colors() {
+            let fixed: Hsl16 = color.into();
+            let float: Hsl = color.into();
+            assert!((fixed.saturation as f32 / 65535.0 - float.saturation).abs() < 1e-4);
+            assert!((fixed.lightness as f32 / 65535.0 - float.lightness).abs() < 1e-4);
+            if fixed.saturation != 0 {
+                let hue = fixed.hue as f32 * 360.0 / 65536.0;
+                let difference = (hue - float.hue.to_positive_degrees()).abs();
+                assert!(difference < 0.01 || difference > 359.99, "{:?}", color);
+            }
+
+            let fixed: Hsv16 = color.into();
+            let float: Hsv = color.into();
+            assert!((fixed.saturation as f32 / 65535.0 - float.saturation).abs() < 1e-4);
+            assert!((fixed.value as f32 / 65535.0 - float.value).abs() < 1e-4);
+        }
+
+        for hue in (0..360).step_by(7) {
+            for saturation in (0..=100).step_by(10) {
+                for lightness in (0..=100).step_by(5) {
+                    let float = Hsl::new(
+                        hue as f32,
+                        saturation as f32 / 100.0,
+                        lightness as f32 / 100.0,
+                    );
+                    assert_within_one(Color::from_hsl(hue, saturation, lightness), float.into());
+
+                    let float = Hsv::new(
+                        hue as f32,
+                        saturation as f32 / 100.0,
+                        lightness as f32 / 100.0,
+                    );
+                    let fixed = Hsv16 {
+                        hue: hue_from_degrees(hue),
+                        saturation: div_round(saturation as u32 * 65535, 100) as u16,
+                        value: div_round(lightness as u32 * 65535, 100) as u16,
+                    };
+                    assert_within_one(fixed.into(), float.into());
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "float")]
+    fn linear_mix_hsl_matches_float() {
+        let factors = [0, 1, 51, 85, 127, 128, 200, 254, 255];
+        // Rounding can decide which way to go if the hues are (almost) equal
+        // or opposite. The results are equally valid, so skip those.
+        let is_ambiguous = |start: Color, end: Color| {
+            let difference = Hsl16::from(end).hue.wrapping_sub(Hsl16::from(start).hue);
+            let distance = difference.min(difference.wrapping_neg());
+            !(64..=0x8000 - 64).contains(&distance)
+        };
+        for start in sample_colors().step_by(5) {
+            for end in sample_colors().step_by(3) {
+                if is_ambiguous(start, end) {
+                    continue;
+                }
+                for factor in factors.iter().map(|bits| U0F8::from_bits(*bits)) {
+                    for hue_arc in [HueArc::Shortest, HueArc::Longest].iter() {
+                        assert_within_one(
+                            start.linear_mix_hsl(end, factor, *hue_arc),
+                            float::linear_mix_hsl(start, end, factor, *hue_arc),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn blend_modes() {
+        let below = Color::new(0, 128, 255);
+        let top = Color::new(128, 128, 128);
+        let blend = |mode| below.blend(top, mode, U0F8::MAX);
+        assert_eq!(blend(BlendMode::Replace), top);
+        assert_eq!(blend(BlendMode::Alpha), top);
+        assert_eq!(blend(BlendMode::Add), Color::new(128, 255, 255));
+        assert_eq!(blend(BlendMode::Multiply), Color::new(0, 64, 128));
+        assert_eq!(blend(BlendMode::Screen), Color::new(128, 192, 255));
+        assert_eq!(blend(BlendMode::Lighten), Color::new(128, 128, 255));
+
+        // Neutral colors keep the color below
+        assert_eq!(
+            below.blend(Color::white(), BlendMode::Multiply, U0F8::MAX),
+            below
+        );
+        assert_eq!(
+            below.blend(Color::black(), BlendMode::Screen, U0F8::MAX),
+            below
+        );
+        assert_eq!(
+            below.blend(Color::black(), BlendMode::Add, U0F8::MAX),
+            below
+        );
+
+        // Opacity weakens all modes except Replace
+        assert_eq!(below.blend(top, BlendMode::Replace, U0F8!(0)), top);
+        assert_eq!(below.blend(top, BlendMode::Alpha, U0F8!(0)), below);
+        assert_eq!(below.blend(top, BlendMode::Add, U0F8!(0)), below);
+        assert_eq!(
+            below.blend(top, BlendMode::Alpha, U0F8!(0.5)),
+            Color::new(64, 128, 192)
+        );
+    }
+
+    #[test]
+    fn perceptual_mixes() {
+        let colors = [
+            Color::black(),
+            Color::white(),
+            Color::new(255, 0, 0),
+            Color::new(12, 200, 99),
+            Color::new(80, 80, 80),
+        ];
+        for start in colors.iter() {
+            for end in colors.iter() {
+                assert_eq!(start.linear_mix_oklab(*end, U0F8::MIN), *start);
+                assert_eq!(start.linear_mix_oklab(*end, U0F8::MAX), *end);
+                for hue_arc in [HueArc::Shortest, HueArc::Longest].iter() {
+                    assert_eq!(start.linear_mix_oklch(*end, U0F8::MIN, *hue_arc), *start);
+                    assert_eq!(start.linear_mix_oklch(*end, U0F8::MAX, *hue_arc), *end);
+                }
+            }
+        }
+
+        // Lightness changes evenly
+        let red = Color::new(255, 0, 0);
+        let blue = Color::new(0, 0, 255);
+        let [start_l, _, _] = oklab::to_lab(red);
+        let [end_l, _, _] = oklab::to_lab(blue);
+        for factor in (0..=u8::MAX).step_by(15) {
+            let factor = U0F8::from_bits(factor);
+            let expected = start_l + (end_l - start_l) * factor.to_bits() as i64 / 255;
+            let [l, _, _] = oklab::to_lab(red.linear_mix_oklab(blue, factor));
+            // 8 bit colors can't hit the exact lightness
+            assert!((l - expected).abs() < 1 << 17);
+        }
+
+        // The shortest way from red to blue is through purple, the longest through green
+        let shortest = red.linear_mix_oklch(blue, U0F8!(0.5), HueArc::Shortest);
+        let longest = red.linear_mix_oklch(blue, U0F8!(0.5), HueArc::Longest);
+        let [_, shortest_green, _]: [u8; 3] = shortest.into();
+        let [_, longest_green, _]: [u8; 3] = longest.into();
+        assert!(shortest_green < 50 && longest_green > 100);
+
+        // Greys keep the hue of the other color
+        let grey = Color::new(128, 128, 128);
+        let [_, grey_to_red_a, grey_to_red_b] =
+            oklab::to_lab(grey.linear_mix_oklch(red, U0F8!(0.5), HueArc::Shortest));
+        let [_, red_a, red_b] = oklab::to_lab(red);
+        assert_eq!(
+            oklab::to_polar([grey_to_red_a, grey_to_red_b]).1 >> 10,
+            oklab::to_polar([red_a, red_b]).1 >> 10
+        );
+    }
 }
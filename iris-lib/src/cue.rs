@@ -1,13 +1,25 @@
-use crate::color::Color;
-use core::num::{NonZeroU16, NonZeroU8};
+use crate::color::{Color, HueArc, OklabPair};
+use crate::easing::Easing;
+use crate::gradient::Gradient;
+use crate::phase::{PhaseMap, Symmetry, FULL_TURN};
+use crate::render::Render;
+use crate::twinkle::Twinkle;
+use crate::wide::{interpolate_steps, widen_factor, WideColor};
+use core::cmp::Ordering;
+use core::fmt;
+use core::num::{NonZeroU16, NonZeroU32, NonZeroU8};
+use fixed::types::U0F16;
 use fixed::types::U0F8; // 8-Bit fixed point number between 0 and 1
-use serde::{Deserialize, Serialize};
+use serde::de::value::EnumAccessDeserializer;
+use serde::de::{EnumAccess, Error, IntoDeserializer, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 
-/// Number of RGB-LEDs. The total number of LEDs to drive is three times this.
+/// Number of RGB-LEDs on the Iris 16. The total number of LEDs to drive is three times this.
+/// Cues can be used with other numbers of LEDs by specifying it as a generic parameter.
 pub const CHANNELS: u8 = 12;
 
 /// The algorithm used for transitioning between two colors.
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub enum RampType {
     /// Hard cut, no interpolation between colors
     Jump,
@@ -17,88 +29,303 @@ pub enum RampType {
     /// Interpolate H, S and L linearly. Will always look nice, but may lead
     /// to undesired additional colors in between.
     LinearHSL {
-        /// If true, jump over the gap between Hue 0° and 360°.
-        /// For example, this allows to transition from yellow to pink through
-        /// red, instead of through green, cyan and blue.
-        wrap_hue: bool,
+        /// Which way around the color wheel the hue moves, see [`HueArc`]
+        #[serde(alias = "wrap_hue", deserialize_with = "deserialize_hue_arc")]
+        hue_arc: HueArc,
     },
+    /// Like [`RampType::LinearRGB`], but follows the given curve instead of
+    /// changing at a constant rate
+    EasedRGB { easing: Easing },
+    /// Like [`RampType::LinearHSL`], but follows the given curve instead of
+    /// changing at a constant rate
+    EasedHSL { easing: Easing, hue_arc: HueArc },
+    /// Interpolate in the perceptual OKLab color space. Colors in between keep
+    /// an even brightness and don't turn grey like with [`RampType::LinearRGB`]
+    LinearOklab,
+    /// Interpolate lightness, chroma and hue in the perceptual OKLCh color space.
+    /// Like [`RampType::LinearHSL`], but with an even brightness
+    LinearOklch {
+        /// Which way around the color wheel the hue moves, see [`HueArc`]
+        hue_arc: HueArc,
+    },
+    /// Like [`RampType::LinearOklab`], but follows the given curve instead of
+    /// changing at a constant rate
+    EasedOklab { easing: Easing },
+    /// Like [`RampType::LinearOklch`], but follows the given curve instead of
+    /// changing at a constant rate
+    EasedOklch { easing: Easing, hue_arc: HueArc },
+}
+
+/// Read the `hue_arc` of [`RampType::LinearHSL`], which used to be stored as
+/// `wrap_hue: bool`. `true` jumped over the gap between 0° and 360° like
+/// [`HueArc::Shortest`], `false` went the long way, like the rainbow preset does.
+/// Binary formats never stored the old form, so they don't need to be self-describing.
+fn deserialize_hue_arc<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HueArc, D::Error> {
+    struct HueArcVisitor;
+
+    impl<'de> Visitor<'de> for HueArcVisitor {
+        type Value = HueArc;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "a HueArc or a boolean")
+        }
+
+        fn visit_bool<E: Error>(self, wrap_hue: bool) -> Result<HueArc, E> {
+            Ok(if wrap_hue {
+                HueArc::Shortest
+            } else {
+                HueArc::Longest
+            })
+        }
+
+        fn visit_str<E: Error>(self, value: &str) -> Result<HueArc, E> {
+            HueArc::deserialize(value.into_deserializer())
+        }
+
+        fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<HueArc, A::Error> {
+            HueArc::deserialize(EnumAccessDeserializer::new(data))
+        }
+    }
+
+    if !deserializer.is_human_readable() {
+        return HueArc::deserialize(deserializer);
+    }
+    deserializer.deserialize_any(HueArcVisitor)
+}
+
+impl RampType {
+    /// The curve applied to the mixing factor. Linear for all non-eased types
+    pub fn easing(&self) -> Easing {
+        match *self {
+            RampType::EasedRGB { easing }
+            | RampType::EasedHSL { easing, .. }
+            | RampType::EasedOklab { easing }
+            | RampType::EasedOklch { easing, .. } => easing,
+            _ => Easing::Linear,
+        }
+    }
+
+    /// Mix two colors in the color space of this RampType.
+    /// [`RampType::Jump`] has no color space, so it returns the start color.
+    /// Easing is not applied here, see [`RampType::easing`]
+    pub fn mix(&self, start: Color, end: Color, factor: U0F8) -> Color {
+        match *self {
+            RampType::Jump => start,
+            RampType::LinearRGB | RampType::EasedRGB { .. } => start.linear_mix_rgb(&end, factor),
+            RampType::LinearHSL { hue_arc } | RampType::EasedHSL { hue_arc, .. } => {
+                start.linear_mix_hsl(end, factor, hue_arc)
+            }
+            RampType::LinearOklab | RampType::EasedOklab { .. } => {
+                start.linear_mix_oklab(end, factor)
+            }
+            RampType::LinearOklch { hue_arc } | RampType::EasedOklch { hue_arc, .. } => {
+                start.linear_mix_oklch(end, factor, hue_arc)
+            }
+        }
+    }
+
+    /// Like [`RampType::mix`], but with 16 bits. RGB is interpolated exactly,
+    /// the other color spaces linearly between the 256 steps of the 8 bit version.
+    pub fn mix_wide(&self, start: Color, end: Color, factor: U0F16) -> WideColor {
+        match *self {
+            RampType::Jump => start.into(),
+            RampType::LinearRGB | RampType::EasedRGB { .. } => {
+                WideColor::from(start).linear_mix_rgb(&end.into(), factor)
+            }
+            ramp_type => interpolate_steps(factor, |factor| ramp_type.mix(start, end, factor)),
+        }
+    }
+}
+
+/// How often a Cue is played. Cues that end hold their last frame.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, Default)]
+pub enum Playback {
+    /// Repeat forever
+    #[default]
+    Loop,
+    /// Repeat forever, playing every other period backwards
+    PingPong,
+    /// Play a single period
+    Once,
+    /// Play the given number of periods
+    Repeat(NonZeroU16),
+}
+
+impl Playback {
+    /// Total number of periods to play, or `None` if it is repeated forever
+    pub fn periods(&self) -> Option<u16> {
+        match *self {
+            Playback::Loop | Playback::PingPong => None,
+            Playback::Once => Some(1),
+            Playback::Repeat(count) => Some(count.get()),
+        }
+    }
+
+    /// Time when the last period ends, if there is one.
+    /// Might not fit into a u32 for long Cues with many repetitions.
+    fn end_ms(&self, duration: u32) -> Option<u64> {
+        self.periods()
+            .map(|periods| periods as u64 * duration as u64)
+    }
 }
 
 /// Newtype implementation of a fixed-point number x, where 0 ≤ x < 1
 /// Will serialize into an [`f32`]
-#[derive(Clone, Copy, Serialize, Deserialize)]
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Default)]
 #[serde(from = "f32")]
 #[serde(into = "f32")]
-pub struct RampRatio(U0F8);
+pub struct Fraction(pub(crate) U0F8);
+
+impl From<Fraction> for f32 {
+    fn from(value: Fraction) -> f32 {
+        value.0.to_num()
+    }
+}
+
+impl From<f32> for Fraction {
+    fn from(value: f32) -> Fraction {
+        Fraction(U0F8::saturating_from_num(value))
+    }
+}
 
-impl From<RampRatio> for f32 {
-    fn from(value: RampRatio) -> f32 {
-        value.into()
+impl From<U0F8> for Fraction {
+    fn from(value: U0F8) -> Fraction {
+        Fraction(value)
     }
 }
 
-impl From<f32> for RampRatio {
-    fn from(value: f32) -> RampRatio {
-        RampRatio(U0F8::saturating_from_num(value))
+/// Reasons why a [`Cue`] or a value for one of its fields is invalid
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CueError {
+    /// There is no LED with this channel, channels go from 0 to N - 1
+    InvalidChannel(u8),
+    /// A duration of 0 ms was given, Cues need at least 1 ms
+    ZeroDuration,
+    /// Durations can't be longer than u32::MAX ms, which is about 49 days
+    DurationTooLong,
+    /// The ramp ratio has to be between 0 and 1
+    InvalidRampRatio,
+    /// A Cue that is repeated has to be played at least once
+    ZeroRepeats,
+    /// A time divisor of 0 was given, it needs to be at least 1
+    ZeroTimeDivisor,
+    /// The axis of a [`Symmetry`] isn't on the ring. It counts half LEDs,
+    /// so it has to be below 2 * N
+    InvalidSymmetryAxis(u8),
+}
+
+impl fmt::Display for CueError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CueError::InvalidChannel(channel) => write!(f, "channel {} doesn't exist", channel),
+            CueError::ZeroDuration => write!(f, "duration must be at least 1 ms"),
+            CueError::DurationTooLong => write!(f, "duration must be at most {} ms", u32::MAX),
+            CueError::InvalidRampRatio => write!(f, "ramp ratio must be between 0 and 1"),
+            CueError::ZeroRepeats => write!(f, "number of repeats must be at least 1"),
+            CueError::ZeroTimeDivisor => write!(f, "time divisor must be at least 1"),
+            CueError::InvalidSymmetryAxis(axis) => {
+                write!(f, "symmetry axis {} is outside of the ring", axis)
+            }
+        }
     }
 }
 
 /// A simple animation that transitions between two colors cyclically.
 /// It transitions from the start color to the end color and then back.
-#[derive(Serialize, Deserialize)]
-pub struct Cue {
+///
+/// `N` is the number of RGB-LEDs in the ring, which defaults to [`CHANNELS`].
+/// It has to be between 1 and 255.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Cue<const N: usize = { CHANNELS as usize }> {
     /// Each LED can be turned off. This is only relevant when using the
-    /// Cue in a Schedule
-    pub channels: [bool; CHANNELS as usize],
+    /// Cue in a [`Schedule`](crate::schedule::Schedule)
+    #[serde(with = "crate::serde_array")]
+    pub channels: [bool; N],
     /// Play the Cue in reverse
     pub reverse: bool,
-    /// Repeat the pattern after reaching a certain LED. Examples values:
+    /// Repeat the pattern after reaching a certain LED. Examples values for 12 LEDs:
     /// - *12*: One full rotation with no visible seams
     /// - *6*: Two moving elements with no visible seams
     /// - *4*: Three moving elements with no visible seams
     /// - *1*: All LEDs are animated in the same manner
+    ///
+    /// Ignored if a `phase_map` is set.
     pub time_divisor: NonZeroU8,
+    /// Offset each LED individually instead of evenly, see [`PhaseMap`].
+    /// Reversing the Cue negates all offsets.
+    #[serde(default)]
+    pub phase_map: Option<PhaseMap<N>>,
+    /// Mirror the animation along an axis, see [`Symmetry`].
+    /// Applied before looking up the offsets of the `phase_map`.
+    #[serde(default)]
+    pub symmetry: Option<Symmetry>,
     /// The duration until the animation repeats.
-    // u16 is enough for 65 seconds, we don't need more than that and it makes
-    // sure the calculations don't overflow when applying the ramp ratio.
-    pub duration_ms: NonZeroU16,
+    /// Cues up to 65 seconds ([`u16::MAX`]) are calculated with faster 32-bit math,
+    /// longer ones with 64-bit math, which is slower on most microcontrollers.
+    pub duration_ms: NonZeroU32,
     /// The algorithm to use for transitioning between the two colors.
     /// Also see [`RampType`]
     pub ramp_type: RampType,
     /// The ratio between the transition from start to end and end to start between 0 and 1
     /// We use an 8-bit fixed point number as this gives a sufficient step size of
     /// ~0.004 and makes sure calculations don't overflow inside u32 registers
-    pub ramp_ratio: RampRatio,
+    pub ramp_ratio: Fraction,
     /// The color to start from
     pub start_color: Color,
     /// The color to transition to
     pub end_color: Color,
+    /// Transition through multiple colors instead. If set, the start and end
+    /// color are ignored and the [`Gradient`] is traversed from its first to
+    /// its last stop and then back.
+    #[serde(default)]
+    pub gradient: Option<Gradient>,
+    /// Let LEDs flash up at random on top of the animation
+    #[serde(default)]
+    pub twinkle: Option<Twinkle>,
+    /// Loop forever or stop after some time, see [`Playback`]
+    #[serde(default)]
+    pub playback: Playback,
 }
 
-impl Default for Cue {
+impl<const N: usize> Default for Cue<N> {
     /// Default Cue will be black, change at least the colors to make it visible!
-    fn default() -> Cue {
+    fn default() -> Cue<N> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID_CHANNELS;
         Cue {
-            channels: [true; CHANNELS as usize],
+            channels: [true; N],
             reverse: false,
-            time_divisor: NonZeroU8::new(CHANNELS).unwrap(),
-            duration_ms: NonZeroU16::new(1000).unwrap(), // Don't set to 0, otherwise the Cue would be invisible
+            time_divisor: NonZeroU8::new(N as u8).unwrap(),
+            phase_map: None,
+            symmetry: None,
+            duration_ms: NonZeroU32::new(1000).unwrap(), // Don't set to 0, otherwise the Cue would be invisible
             ramp_type: RampType::Jump,
             ramp_ratio: 0.5.into(), // Don't set to 0, the start color would be invisible
 
             // Set colors to black, those should be changed!
             start_color: Color::black(),
             end_color: Color::black(),
+            gradient: None,
+            twinkle: None,
+            playback: Playback::Loop,
         }
     }
 }
 
-impl Cue {
+impl<const N: usize> Cue<N> {
+    /// Fails compilation if N is out of range
+    const VALID_CHANNELS: () = assert!(
+        N > 0 && N <= u8::MAX as usize,
+        "A Cue needs between 1 and 255 channels"
+    );
+
     /// Create pre-built Cue displaying a clockwise rotating rainbow
-    pub fn rainbow() -> Cue {
+    pub fn rainbow() -> Cue<N> {
         Cue {
-            duration_ms: NonZeroU16::new(3000).unwrap(),
-            ramp_type: RampType::LinearHSL { wrap_hue: false },
+            duration_ms: NonZeroU32::new(3000).unwrap(),
+            ramp_type: RampType::LinearHSL {
+                hue_arc: HueArc::Longest,
+            },
             ramp_ratio: 1.0.into(),
             start_color: Color::from_hsl(0, 100, 50),
             end_color: Color::from_hsl(359, 100, 50),
@@ -107,9 +334,9 @@ impl Cue {
     }
 
     /// Create pre-built Cue displaying a clockwise rotating black and white half
-    pub fn black_white_jump() -> Cue {
+    pub fn black_white_jump() -> Cue<N> {
         Cue {
-            duration_ms: NonZeroU16::new(3000).unwrap(),
+            duration_ms: NonZeroU32::new(3000).unwrap(),
             start_color: Color::white(),
             end_color: Color::black(),
             ..Default::default()
@@ -117,10 +344,12 @@ impl Cue {
     }
 
     /// Create pre-built Cue displaying a white breathing effect
-    pub fn white_breathing() -> Cue {
+    pub fn white_breathing() -> Cue<N> {
         Cue {
-            duration_ms: NonZeroU16::new(3600).unwrap(),
-            ramp_type: RampType::LinearRGB,
+            duration_ms: NonZeroU32::new(3600).unwrap(),
+            ramp_type: RampType::EasedRGB {
+                easing: Easing::InOutSine,
+            },
             ramp_ratio: 0.4.into(),
             time_divisor: NonZeroU8::new(1).unwrap(),
             start_color: Color::black(),
@@ -129,36 +358,247 @@ impl Cue {
         }
     }
 
+    /// Create pre-built Cue displaying a rotating sunset from deep purple over red to yellow
+    pub fn sunset() -> Cue<N> {
+        let mut gradient = Gradient::new();
+        gradient
+            .add(0.0.into(), Color::from_hsl(270, 100, 25))
+            .unwrap();
+        gradient
+            .add(0.4.into(), Color::from_hsl(350, 100, 50))
+            .unwrap();
+        gradient
+            .add(0.7.into(), Color::from_hsl(20, 100, 50))
+            .unwrap();
+        gradient
+            .add(1.0.into(), Color::from_hsl(45, 100, 50))
+            .unwrap();
+        Cue {
+            duration_ms: NonZeroU32::new(6000).unwrap(),
+            // Crosses 0° between the second and third stop
+            ramp_type: RampType::LinearHSL {
+                hue_arc: HueArc::Shortest,
+            },
+            gradient: Some(gradient),
+            ..Default::default()
+        }
+    }
+
+    /// Create pre-built Cue displaying white stars twinkling on a slowly shifting night sky
+    pub fn starry_sky() -> Cue<N> {
+        Cue {
+            duration_ms: NonZeroU32::new(20000).unwrap(),
+            ramp_type: RampType::EasedHSL {
+                easing: Easing::InOutSine,
+                hue_arc: HueArc::Shortest,
+            },
+            start_color: Color::from_hsl(230, 100, 8),
+            end_color: Color::from_hsl(260, 100, 15),
+            twinkle: Some(Twinkle {
+                seed: 0x5EED,
+                color: Color::white(),
+                density: 0.15.into(),
+                decay_ms: NonZeroU16::new(1200).unwrap(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Check the parts of a Cue that its types can't guarantee. Cues from
+    /// untrusted sources, e.g. deserialized ones, should be validated first.
+    /// # Examples
+    /// ```
+    /// use iris_lib::cue::{Cue, CueError};
+    /// use iris_lib::phase::Symmetry;
+    ///
+    /// let mut cue: Cue = Cue::rainbow();
+    /// assert_eq!(cue.validate(), Ok(()));
+    /// cue.symmetry = Some(Symmetry { axis: 30 });
+    /// assert_eq!(cue.validate(), Err(CueError::InvalidSymmetryAxis(30)));
+    /// ```
+    pub fn validate(&self) -> Result<(), CueError> {
+        match self.symmetry {
+            Some(symmetry) if symmetry.axis as usize >= 2 * N => {
+                Err(CueError::InvalidSymmetryAxis(symmetry.axis))
+            }
+            _ => Ok(()),
+        }
+    }
+
     /// Calculate the Color of a single LED at a given point in time
+    /// # Panics
+    /// Panics if the channel doesn't exist, see [`Cue::try_current_color`]
     pub fn current_color(&self, time_ms: u32, channel: u8) -> Color {
-        let progress = self.progress(time_ms, channel);
+        assert!((channel as usize) < N);
+        self.color_in_frame(&self.prepare_frame(time_ms), channel)
+    }
+
+    /// Like [`Cue::current_color`], but returns an error instead of panicking
+    /// # Examples
+    /// ```
+    /// use iris_lib::cue::{Cue, CueError};
+    ///
+    /// let cue: Cue = Cue::rainbow();
+    /// assert_eq!(cue.try_current_color(0, 3), Ok(cue.current_color(0, 3)));
+    /// assert_eq!(cue.try_current_color(0, 12), Err(CueError::InvalidChannel(12)));
+    /// ```
+    pub fn try_current_color(&self, time_ms: u32, channel: u8) -> Result<Color, CueError> {
+        self.check_channel(channel)?;
+        Ok(self.color_in_frame(&self.prepare_frame(time_ms), channel))
+    }
+
+    /// Error if the channel doesn't exist
+    pub(crate) fn check_channel(&self, channel: u8) -> Result<(), CueError> {
+        if (channel as usize) < N {
+            Ok(())
+        } else {
+            Err(CueError::InvalidChannel(channel))
+        }
+    }
+
+    // Calculate the Color for a given progress of the animation
+    fn color_at_progress(&self, timing: &FrameTiming, progress: U0F8) -> Color {
+        if let Some(gradient) = &self.gradient {
+            return gradient.color_at(self.mixing_factor(progress), self.ramp_type);
+        }
 
         match self.ramp_type {
             RampType::Jump => self.color_jump(progress),
-            RampType::LinearRGB => self
-                .start_color
-                .linear_mix_rgb(&self.end_color, self.mixing_factor(progress)),
-            RampType::LinearHSL { wrap_hue } => self.start_color.linear_mix_hsl(
+            _ => self.mix_endpoints(timing, self.mixing_factor(progress)),
+        }
+    }
+
+    /// Mix the start and the end color, reusing the colors that were
+    /// converted once for the whole frame, see [`Cue::prepare_frame`]
+    fn mix_endpoints(&self, timing: &FrameTiming, factor: U0F8) -> Color {
+        match (self.ramp_type, &timing.oklab) {
+            (RampType::LinearOklch { hue_arc }, Some(oklab))
+            | (RampType::EasedOklch { hue_arc, .. }, Some(oklab)) => {
+                oklab.mix_polar(factor, hue_arc)
+            }
+            (_, Some(oklab)) => oklab.mix(factor),
+            (ramp_type, None) => ramp_type.mix(self.start_color, self.end_color, factor),
+        }
+    }
+
+    /// Calculate the Color of a single LED, reusing the values that were
+    /// calculated once for the whole frame
+    pub(crate) fn color_in_frame(&self, timing: &FrameTiming, channel: u8) -> Color {
+        let color = self.color_at_progress(timing, timing.progress(channel));
+        self.add_twinkle(color, timing.time_ms, channel)
+    }
+
+    /// Like [`Cue::current_color`], but with 16 bits per component, for LED
+    /// drivers with a higher resolution. The progress is calculated with 16
+    /// bits as well, so slow fades are much smoother.
+    /// # Examples
+    /// ```
+    /// use iris_lib::color::Color;
+    /// use iris_lib::cue::Cue;
+    /// use iris_lib::wide::WideColor;
+    ///
+    /// let cue: Cue = Cue::white_breathing();
+    /// assert_eq!(cue.current_wide_color(0, 0), WideColor::black());
+    /// assert_eq!(cue.current_wide_color(1440, 0), WideColor::white());
+    /// // The values in between aren't limited to the 256 steps of Color
+    /// let wide = cue.current_wide_color(1000, 0);
+    /// assert_ne!(wide, WideColor::from(Color::from(wide)));
+    /// ```
+    /// # Panics
+    /// Panics if the channel doesn't exist
+    pub fn current_wide_color(&self, time_ms: u32, channel: u8) -> WideColor {
+        assert!((channel as usize) < N);
+        self.wide_color_in_frame(&self.prepare_frame(time_ms), channel)
+    }
+
+    /// Like [`Render::render_into`], but with 16 bits per component,
+    /// see [`Cue::current_wide_color`]
+    pub fn render_wide_into(&self, time_ms: u32, frame: &mut [WideColor]) {
+        let timing = self.prepare_frame(time_ms);
+        for (channel, color) in (0..=timing.last_channel).zip(frame.iter_mut()) {
+            *color = self.wide_color_in_frame(&timing, channel);
+        }
+    }
+
+    fn wide_color_in_frame(&self, timing: &FrameTiming, channel: u8) -> WideColor {
+        let progress = timing.wide_progress(channel);
+        let color = match (&self.gradient, self.ramp_type) {
+            // Interpolating between two steps would blur the hard cut
+            (_, RampType::Jump) => self
+                .color_at_progress(timing, timing.progress(channel))
+                .into(),
+            (Some(gradient), ramp_type) => {
+                interpolate_steps(self.wide_mixing_factor(progress), |position| {
+                    gradient.color_at(position, ramp_type)
+                })
+            }
+            // Mixing in OKLab can't be interpolated exactly either, but reuses the converted colors
+            (None, _) if timing.oklab.is_some() => {
+                interpolate_steps(self.wide_mixing_factor(progress), |factor| {
+                    self.mix_endpoints(timing, factor)
+                })
+            }
+            (None, ramp_type) => ramp_type.mix_wide(
+                self.start_color,
                 self.end_color,
-                self.mixing_factor(progress),
-                wrap_hue,
+                self.wide_mixing_factor(progress),
             ),
+        };
+        match &self.twinkle {
+            Some(twinkle) => {
+                let intensity = twinkle.intensity(timing.time_ms, channel);
+                color.linear_mix_rgb(&twinkle.color.into(), widen_factor(intensity))
+            }
+            None => color,
+        }
+    }
+
+    // Like mixing_factor, but with 16 bits
+    fn wide_mixing_factor(&self, progress: U0F16) -> U0F16 {
+        let ramp_ratio = widen_factor(self.ramp_ratio.0);
+        // Same triangle wave as in linear_mixing_factor
+        let linear = match progress.cmp(&ramp_ratio) {
+            Ordering::Less => progress.saturating_div(ramp_ratio),
+            Ordering::Equal => U0F16::MAX,
+            Ordering::Greater => {
+                U0F16::MAX - (progress - ramp_ratio).saturating_div(U0F16::MAX - ramp_ratio)
+            }
+        };
+        self.ramp_type.easing().apply_wide(linear)
+    }
+
+    // Apply the twinkle effect on top of the color of the animation, if set
+    fn add_twinkle(&self, color: Color, time_ms: u32, channel: u8) -> Color {
+        match &self.twinkle {
+            Some(twinkle) => twinkle.apply(color, time_ms, channel),
+            None => color,
         }
     }
 
-    // Calculate factor for color mixing
+    // Calculate factor for color mixing, with the easing curve applied
     fn mixing_factor(&self, progress: U0F8) -> U0F8 {
+        self.ramp_type
+            .easing()
+            .apply(self.linear_mixing_factor(progress))
+    }
+
+    // Calculate factor for color mixing as a triangle wave
+    fn linear_mixing_factor(&self, progress: U0F8) -> U0F8 {
         // In theory, the maximum value that can occur is 1, but U0F8 can't represent that,
         // so we use saturating division, which prevents an overflow.
-        if progress <= self.ramp_ratio.0 {
+        match progress.cmp(&self.ramp_ratio.0) {
             // Actual formula:
             // progress / ramp_ratio
-            progress.saturating_div(self.ramp_ratio.0)
-        } else {
+            Ordering::Less => progress.saturating_div(self.ramp_ratio.0),
+            // Handled separately, as this would divide by 0 for a ramp ratio of 0
+            Ordering::Equal => U0F8::MAX,
             // We have to use saturating division here as well due to rounding errors
             // We also use U0F8::MAX instead of 1. Actual formula:
             // 1 - (progress - ramp_ratio)/(1 - ramp_ratio)
-            U0F8::MAX - (progress - self.ramp_ratio.0).saturating_div(U0F8::MAX - self.ramp_ratio.0)
+            Ordering::Greater => {
+                U0F8::MAX
+                    - (progress - self.ramp_ratio.0).saturating_div(U0F8::MAX - self.ramp_ratio.0)
+            }
         }
     }
 
@@ -174,18 +614,19 @@ impl Cue {
     /// Return a fraction of how far the animation has progressed for the specified LED
     /// # Examples:
     /// ```
-    /// use iris_lib::color::Color;
+    /// use iris_lib::builder::CueBuilder;
     /// use iris_lib::cue::Cue;
     /// use fixed::types::U0F8;
     /// use fixed_macro::types::U0F8;
-    /// use core::num::NonZeroU16
+    /// use core::num::NonZeroU8;
+    /// use core::time::Duration;
     ///
-    /// let mut cue = Cue {
-    ///     reverse: true,  // Reverse makes the numbers a little nicer
-    ///     duration_ms: NonZeroU16::new(1200).unwrap(),
-    ///     time_divisor: 12,
-    ///     .. Default::default()
-    /// };
+    /// let mut cue: Cue = CueBuilder::new()
+    ///     .reverse(true) // Reverse makes the numbers a little nicer
+    ///     .duration(Duration::from_millis(1200))
+    ///     .time_divisor(12)
+    ///     .build()
+    ///     .unwrap();
     ///
     /// assert_eq!(cue.progress(0,0), U0F8!(0));
     /// assert_eq!(cue.progress(600,0), U0F8!(0.5));
@@ -194,39 +635,219 @@ impl Cue {
     /// assert_eq!(cue.progress(0,2), cue.progress(600,8));
     /// assert_eq!(cue.progress(300,3), cue.progress(900,9));
     /// // wraps around
-    /// assert_eq!(cue.progress(1200,0), 0);
-    /// cue.time_divisor = 6;
+    /// assert_eq!(cue.progress(1200,0), U0F8!(0));
+    /// cue.time_divisor = NonZeroU8::new(6).unwrap();
     /// assert_eq!(cue.progress(200,1), cue.progress(200,7));
     /// ```
     pub fn progress(&self, time_ms: u32, channel: u8) -> U0F8 {
-        assert!(channel < CHANNELS);
+        assert!((channel as usize) < N);
+        self.frame_timing(time_ms).progress(channel)
+    }
 
-        // Handle reversed cue
-        let channel = if self.reverse {
-            channel
-        } else {
-            // In non-reverse, lower channels need a higher progress
-            CHANNELS - 1 - channel
+    /// Like [`Cue::progress`], but returns an error instead of panicking
+    /// if the channel doesn't exist
+    pub fn try_progress(&self, time_ms: u32, channel: u8) -> Result<U0F8, CueError> {
+        self.check_channel(channel)?;
+        Ok(self.frame_timing(time_ms).progress(channel))
+    }
+
+    /// Whether the Cue has stopped according to its [`Playback`] mode.
+    /// Cues that loop are never finished.
+    /// # Examples
+    /// ```
+    /// use iris_lib::cue::{Cue, Playback};
+    /// use core::num::NonZeroU16;
+    ///
+    /// let cue: Cue = Cue {
+    ///     playback: Playback::Repeat(NonZeroU16::new(2).unwrap()),
+    ///     ..Cue::rainbow()
+    /// };
+    /// assert!(!cue.is_finished(5999));
+    /// assert!(cue.is_finished(6000));
+    /// ```
+    pub fn is_finished(&self, time_ms: u32) -> bool {
+        self.playback
+            .end_ms(self.duration_ms.get())
+            .is_some_and(|end_ms| time_ms as u64 >= end_ms)
+    }
+
+    /// Calculate all values needed for [`Cue::progress`] that are the same
+    /// for all LEDs at a given point in time
+    pub(crate) fn frame_timing(&self, time_ms: u32) -> FrameTiming<'_> {
+        let duration = self.duration_ms.get();
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID_CHANNELS;
+
+        // Finished Cues hold their last frame. end_ms ≤ time_ms, so it fits into a u32
+        let time_ms = match self.playback.end_ms(duration) {
+            Some(end_ms) if time_ms as u64 >= end_ms => (end_ms - 1) as u32,
+            _ => time_ms,
+        };
+        // Wrapping around before adding the offset of each channel
+        // doesn't change the result, but makes sure it can't overflow
+        let time_in_period = match self.playback {
+            Playback::PingPong => {
+                // Twice the duration doesn't always fit into a u32
+                let time_in_periods = (time_ms as u64 % (2 * duration as u64)) as u32;
+                match time_in_periods.checked_sub(duration) {
+                    // Run backwards through the second period
+                    Some(time_backwards) => duration - 1 - time_backwards,
+                    None => time_in_periods,
+                }
+            }
+            _ => time_ms % duration,
+        };
+
+        FrameTiming {
+            time_ms,
+            last_channel: (N - 1) as u8,
+            reverse: self.reverse,
+            duration,
+            time_divisor: self.time_divisor.get() as u32,
+            phase_map: self.phase_map.as_ref().map(|map| &map.offsets[..]),
+            symmetry: self.symmetry,
+            time_in_period,
+            oklab: None,
+        }
+    }
+
+    /// Like [`Cue::frame_timing`], but also converts the colors to OKLab if
+    /// they are mixed there, so this happens once per frame instead of once per LED
+    pub(crate) fn prepare_frame(&self, time_ms: u32) -> FrameTiming<'_> {
+        let oklab = match (&self.gradient, self.ramp_type) {
+            (None, RampType::LinearOklab)
+            | (None, RampType::EasedOklab { .. })
+            | (None, RampType::LinearOklch { .. })
+            | (None, RampType::EasedOklch { .. }) => {
+                Some(OklabPair::new(self.start_color, self.end_color))
+            }
+            _ => None,
+        };
+        FrameTiming {
+            oklab,
+            ..self.frame_timing(time_ms)
+        }
+    }
+}
+
+/// Values that are shared by all LEDs when calculating one frame of a [`Cue`]
+pub(crate) struct FrameTiming<'a> {
+    time_ms: u32,
+    last_channel: u8,
+    reverse: bool,
+    duration: u32,
+    time_divisor: u32,
+    phase_map: Option<&'a [Fraction]>,
+    symmetry: Option<Symmetry>,
+    time_in_period: u32,
+    /// Start and end color in OKLab, if the ramp mixes them there
+    oklab: Option<OklabPair>,
+}
+
+impl FrameTiming<'_> {
+    /// See [`Cue::progress`]
+    fn progress(&self, channel: u8) -> U0F8 {
+        U0F8::from_bits(self.progress_fraction(channel, u8::MAX as u32) as u8)
+    }
+
+    /// Like [`FrameTiming::progress`], but with 16 bits
+    fn wide_progress(&self, channel: u8) -> U0F16 {
+        U0F16::from_bits(self.progress_fraction(channel, u16::MAX as u32) as u16)
+    }
+
+    /// Progress as a fraction of `max`, which is either u8::MAX or u16::MAX
+    fn progress_fraction(&self, channel: u8, max: u32) -> u32 {
+        let channel = match self.symmetry {
+            Some(symmetry) => symmetry.fold(channel, self.last_channel as usize + 1),
+            None => channel,
         };
+        let phase = self.phase(channel);
+        if self.duration <= u16::MAX as u32 {
+            self.short_progress(phase, max)
+        } else {
+            self.long_progress(phase, max)
+        }
+    }
+
+    /// How far the channel is ahead, as a fraction of the duration.
+    /// Returns numerator and denominator, where the numerator is ≤ 0x100
+    fn phase(&self, channel: u8) -> (u32, u32) {
+        match self.phase_map {
+            Some(offsets) => {
+                let offset = offsets[channel as usize].0.to_bits() as u32;
+                // FULL_TURN is a full duration, so this is the same as -offset
+                let offset = if self.reverse {
+                    FULL_TURN as u32 - offset
+                } else {
+                    offset
+                };
+                (offset, FULL_TURN as u32)
+            }
+            None => {
+                // Handle reversed cue
+                let channel = if self.reverse {
+                    channel
+                } else {
+                    // In non-reverse, lower channels need a higher progress
+                    self.last_channel - channel
+                };
+                (channel as u32, self.time_divisor)
+            }
+        }
+    }
 
-        // We need the duration to be u32 in all calculations
-        let duration = self.duration_ms.get() as u32;
-        let time_divisor = self.time_divisor.get() as u32;
+    /// Progress as a fraction of `max` for durations up to [`u16::MAX`],
+    /// which can be calculated in 32 bits
+    fn short_progress(&self, (numerator, denominator): (u32, u32), max: u32) -> u32 {
+        let duration = self.duration;
 
         // Offset calculation for given channel
-        // `+ (time_divisor / 2)` achieves mathematical integer rounding, see https://stackoverflow.com/a/2422722/
-        let time_ms = time_ms + (((duration * channel as u32) + (time_divisor / 2)) / time_divisor);
+        // `+ (denominator / 2)` achieves mathematical integer rounding, see https://stackoverflow.com/a/2422722/
+        // As duration is ≤ 0xFFFF and numerator ≤ 0x100, the product is < 0x0100_0000
+        let time_ms =
+            self.time_in_period + (((duration * numerator) + (denominator / 2)) / denominator);
 
         // Make effect wrap around
-        // As duration_ms is a u16, time_ms is now ≤ 0xFFFE
+        // As duration is ≤ 0xFFFF, time_ms is now ≤ 0xFFFE
         let time_ms = time_ms % duration;
 
-        // Calculate progress as a fraction of u8::MAX
-        // The calculation for all maximum values that can be computed on here would be
-        // 0xFFFE * 0xFF + 0xFFFE) / 0xFFFF = 0xFF
-        // So the result will always fit into a u8.
-        let progress_fraction = (time_ms * u8::MAX as u32 + duration / 2) / duration;
-        U0F8::from_bits(progress_fraction as u8)
+        // As time_ms < duration, the result is ≤ max. The largest intermediate value is
+        // 0xFFFE * 0xFFFF + 0x7FFF, so the calculation always fits into a u32
+        (time_ms * max + duration / 2) / duration
+    }
+
+    /// Progress as a fraction of `max` for any duration.
+    /// Same as [`FrameTiming::short_progress`], but with 64-bit math:
+    /// With duration ≤ 0xFFFF_FFFF, no intermediate value exceeds 0xFFFF * 0xFFFF_FFFF * 2
+    fn long_progress(&self, (numerator, denominator): (u32, u32), max: u32) -> u32 {
+        let duration = self.duration as u64;
+        let (numerator, denominator) = (numerator as u64, denominator as u64);
+
+        let time_ms = self.time_in_period as u64
+            + (((duration * numerator) + (denominator / 2)) / denominator);
+        let time_ms = time_ms % duration;
+
+        ((time_ms * max as u64 + duration / 2) / duration) as u32
+    }
+}
+
+impl<const N: usize> Render<N> for Cue<N> {
+    fn render_into(&self, time_ms: u32, frame: &mut [Color]) {
+        let timing = self.prepare_frame(time_ms);
+        // Neighbouring LEDs often have the same progress, e.g. if all LEDs
+        // are animated in the same manner, so reuse the last color if possible
+        let mut previous: Option<(U0F8, Color)> = None;
+        for (channel, color) in (0..=timing.last_channel).zip(frame.iter_mut()) {
+            let progress = timing.progress(channel);
+            let base_color = match previous {
+                Some((previous_progress, previous_color)) if previous_progress == progress => {
+                    previous_color
+                }
+                _ => self.color_at_progress(&timing, progress),
+            };
+            previous = Some((progress, base_color));
+            *color = self.add_twinkle(base_color, timing.time_ms, channel);
+        }
     }
 }
 
@@ -235,8 +856,493 @@ mod test {
     use crate::cue::*;
     #[test]
     fn create_defaults() {
-        let _ = Cue::rainbow();
-        let _ = Cue::black_white_jump();
-        let _ = Cue::white_breathing();
+        let _: Cue = Cue::rainbow();
+        let _: Cue = Cue::black_white_jump();
+        let _: Cue = Cue::white_breathing();
+        let _: Cue = Cue::sunset();
+        let _: Cue = Cue::starry_sky();
+    }
+
+    #[test]
+    fn deserialize_wrap_hue() {
+        // Serialized from Cue::rainbow() before HueArc replaced wrap_hue. Its
+        // colors were broken by passing percentages to palette at the time.
+        let json = r#"{"channels":[true,true,true,true,true,true,true,true,true,true,true,true],"reverse":false,"time_divisor":12,"duration_ms":3000,"ramp_type":{"LinearHSL":{"wrap_hue":false}},"ramp_ratio":0.99609375,"start_color":{"red":0,"green":255,"blue":255},"end_color":{"red":0,"green":255,"blue":255}}"#;
+        let cue: Cue = serde_json::from_str(json).unwrap();
+        let cyan = Color::new(0, 255, 255);
+        let expected = Cue {
+            start_color: cyan,
+            end_color: cyan,
+            ..Cue::rainbow()
+        };
+        assert_eq!(cue, expected);
+
+        let wrapped: RampType = serde_json::from_str(r#"{"LinearHSL":{"wrap_hue":true}}"#).unwrap();
+        assert_eq!(
+            wrapped,
+            RampType::LinearHSL {
+                hue_arc: HueArc::Shortest
+            }
+        );
+        // The current form still round-trips
+        let rainbow: Cue = Cue::rainbow();
+        let json = serde_json::to_string(&rainbow).unwrap();
+        assert!(json.contains(r#"{"LinearHSL":{"hue_arc":"Longest"}}"#));
+        assert_eq!(serde_json::from_str::<Cue>(&json).unwrap(), rainbow);
+    }
+
+    #[test]
+    fn render_frame_matches_current_color() {
+        let cues: [Cue; 6] = [
+            Cue::rainbow(),
+            Cue::black_white_jump(),
+            Cue::white_breathing(),
+            Cue::sunset(),
+            Cue::starry_sky(),
+            Cue {
+                reverse: true,
+                time_divisor: NonZeroU8::new(5).unwrap(),
+                duration_ms: NonZeroU32::new(777).unwrap(),
+                ..Cue::rainbow()
+            },
+        ];
+        for cue in cues.iter() {
+            for time_ms in (0..7000).step_by(13).chain(u32::MAX - 100..=u32::MAX) {
+                let frame = cue.render_frame(time_ms);
+                for channel in 0..CHANNELS {
+                    assert_eq!(frame[channel as usize], cue.current_color(time_ms, channel));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn oklab_ramps_convert_once() {
+        let ramp_types = [
+            RampType::LinearOklab,
+            RampType::EasedOklab {
+                easing: Easing::InOutSine,
+            },
+            RampType::LinearOklch {
+                hue_arc: HueArc::Longest,
+            },
+            RampType::EasedOklch {
+                easing: Easing::InCubic,
+                hue_arc: HueArc::Shortest,
+            },
+        ];
+        for ramp_type in ramp_types.iter() {
+            let cue: Cue = Cue {
+                ramp_type: *ramp_type,
+                start_color: Color::new(255, 40, 0),
+                end_color: Color::new(0, 90, 200),
+                ..Cue::rainbow()
+            };
+            assert!(cue.frame_timing(0).oklab.is_none());
+            assert!(cue.prepare_frame(0).oklab.is_some());
+            for time_ms in (0..3000).step_by(37) {
+                let frame = cue.render_frame(time_ms);
+                let mut wide_frame = [WideColor::black(); CHANNELS as usize];
+                cue.render_wide_into(time_ms, &mut wide_frame);
+                for channel in 0..CHANNELS {
+                    let factor = cue.mixing_factor(cue.progress(time_ms, channel));
+                    let expected = ramp_type.mix(cue.start_color, cue.end_color, factor);
+                    assert_eq!(frame[channel as usize], expected);
+
+                    let progress = cue.frame_timing(time_ms).wide_progress(channel);
+                    let factor = cue.wide_mixing_factor(progress);
+                    let expected = ramp_type.mix_wide(cue.start_color, cue.end_color, factor);
+                    assert_eq!(wide_frame[channel as usize], expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn render_into_shorter_frame() {
+        let cue: Cue = Cue::rainbow();
+        let mut frame = [Color::white(); 4];
+        cue.render_into(1000, &mut frame);
+        assert_eq!(frame[..], cue.render_frame(1000)[..4]);
+    }
+
+    #[test]
+    fn eased_ramp() {
+        let linear: Cue = Cue {
+            ramp_type: RampType::LinearRGB,
+            ..Cue::white_breathing()
+        };
+        let eased: Cue = Cue::white_breathing();
+
+        // Both reach the start and end color at the same time
+        assert_eq!(eased.current_color(0, 0), linear.current_color(0, 0));
+        assert_eq!(eased.current_color(1440, 0), Color::white());
+        assert_eq!(linear.current_color(1440, 0), Color::white());
+        // But the eased one starts more slowly
+        let linear_red: [u8; 3] = linear.current_color(200, 0).into();
+        let eased_red: [u8; 3] = eased.current_color(200, 0).into();
+        assert!(eased_red[0] < linear_red[0]);
+    }
+
+    #[test]
+    fn gradient_follows_progress() {
+        let cue: Cue = Cue {
+            ramp_type: RampType::LinearRGB,
+            time_divisor: NonZeroU8::new(1).unwrap(),
+            ..Cue::sunset()
+        };
+        let gradient = cue.gradient.unwrap();
+        let stops = gradient.stops();
+
+        // The first stop is shown at the start, the last one at the peak of the ramp
+        assert_eq!(cue.current_color(0, 0), stops[0].color);
+        assert_eq!(cue.current_color(3000, 0), stops[3].color);
+        // Same result as evaluating the gradient directly
+        let progress = cue.progress(1000, 0);
+        assert_eq!(
+            cue.current_color(1000, 0),
+            gradient.color_at(cue.mixing_factor(progress), RampType::LinearRGB)
+        );
+    }
+
+    #[test]
+    fn other_channel_counts() {
+        // One full rotation, no matter how many LEDs the ring has
+        let small: Cue<8> = Cue::rainbow();
+        let large: Cue<24> = Cue::rainbow();
+        assert_eq!(small.time_divisor.get(), 8);
+        assert_eq!(large.time_divisor.get(), 24);
+        // Neighboring LEDs are 1/24 of the duration apart
+        assert_eq!(large.progress(0, 23), U0F8::MIN);
+        assert_eq!(large.progress(0, 22), U0F8::from_bits(11));
+        assert_eq!(large.progress(0, 0), U0F8::from_bits(244));
+
+        let frame = large.render_frame(1234);
+        assert_eq!(frame.len(), 24);
+        for channel in 0..24 {
+            assert_eq!(frame[channel as usize], large.current_color(1234, channel));
+        }
+    }
+
+    #[test]
+    fn long_duration() {
+        // A slow hue drift over 5 minutes
+        let cue: Cue = Cue {
+            duration_ms: NonZeroU32::new(5 * 60 * 1000).unwrap(),
+            ..Cue::rainbow()
+        };
+        assert_eq!(cue.progress(0, 11), U0F8::MIN);
+        assert_eq!(cue.progress(150_000, 11), U0F8::from_bits(128));
+        assert_eq!(cue.progress(299_999, 11), U0F8::MAX);
+        assert_eq!(cue.progress(300_000, 11), U0F8::MIN);
+        // Channel 0 is 11/12 of the duration ahead
+        assert_eq!(cue.progress(0, 0), cue.progress(275_000, 11));
+
+        // Both calculations agree at the boundary between them
+        let short = Cue {
+            duration_ms: NonZeroU32::new(u16::MAX as u32).unwrap(),
+            ..cue.clone()
+        };
+        let timing = short.frame_timing(12345);
+        for channel in 0..CHANNELS {
+            let phase = timing.phase(channel);
+            for max in [u8::MAX as u32, u16::MAX as u32].iter() {
+                assert_eq!(
+                    timing.short_progress(phase, *max),
+                    timing.long_progress(phase, *max)
+                );
+            }
+        }
+
+        // Longest possible duration doesn't overflow
+        let longest = Cue {
+            duration_ms: NonZeroU32::new(u32::MAX).unwrap(),
+            ..cue
+        };
+        assert_eq!(longest.progress(u32::MAX - 1, 11), U0F8::MAX);
+        let frame = longest.render_frame(u32::MAX);
+        assert_eq!(frame[11], longest.current_color(u32::MAX, 11));
+    }
+
+    #[test]
+    fn playback_modes() {
+        let looping: Cue = Cue {
+            time_divisor: NonZeroU8::new(1).unwrap(),
+            ..Cue::rainbow()
+        };
+        let once = Cue {
+            playback: Playback::Once,
+            ..looping.clone()
+        };
+        let twice = Cue {
+            playback: Playback::Repeat(NonZeroU16::new(2).unwrap()),
+            ..looping.clone()
+        };
+        let ping_pong = Cue {
+            playback: Playback::PingPong,
+            ..looping.clone()
+        };
+        let twinkling_once: Cue = Cue {
+            playback: Playback::Once,
+            ..Cue::starry_sky()
+        };
+
+        for time_ms in (0..3000).step_by(7) {
+            assert_eq!(once.progress(time_ms, 0), looping.progress(time_ms, 0));
+            assert_eq!(
+                twice.progress(time_ms + 3000, 0),
+                looping.progress(time_ms, 0)
+            );
+            assert_eq!(ping_pong.progress(time_ms, 0), looping.progress(time_ms, 0));
+            // The second period runs backwards
+            assert_eq!(
+                ping_pong.progress(time_ms + 3000, 0),
+                looping.progress(2999 - time_ms, 0)
+            );
+        }
+
+        // Holds the last frame after finishing
+        assert!(!once.is_finished(2999));
+        assert!(once.is_finished(3000));
+        assert!(!twice.is_finished(3000));
+        assert!(twice.is_finished(6000));
+        assert!(!looping.is_finished(u32::MAX));
+        assert!(!ping_pong.is_finished(u32::MAX));
+        for time_ms in [3000, 10_000, u32::MAX].iter() {
+            assert_eq!(once.progress(*time_ms, 0), U0F8::MAX);
+            assert_eq!(once.render_frame(*time_ms), once.render_frame(2999));
+            assert_eq!(
+                twinkling_once.render_frame(time_ms.saturating_mul(10)),
+                twinkling_once.render_frame(19_999)
+            );
+            assert_eq!(
+                twinkling_once.current_color(time_ms.saturating_mul(10), 3),
+                twinkling_once.current_color(19_999, 3)
+            );
+            assert_eq!(
+                twice.render_frame(time_ms.saturating_mul(2)),
+                twice.render_frame(5999)
+            );
+        }
+
+        // Long Cues that end after u32::MAX never finish
+        let long = Cue {
+            duration_ms: NonZeroU32::new(u32::MAX).unwrap(),
+            playback: Playback::Repeat(NonZeroU16::new(3).unwrap()),
+            ..looping.clone()
+        };
+        assert!(!long.is_finished(u32::MAX));
+        let long_ping_pong = Cue {
+            playback: Playback::PingPong,
+            ..long
+        };
+        assert_eq!(long_ping_pong.progress(u32::MAX - 1, 0), U0F8::MAX);
+    }
+
+    #[test]
+    fn wide_colors() {
+        let cues: [Cue; 5] = [
+            Cue::rainbow(),
+            Cue::black_white_jump(),
+            Cue::white_breathing(),
+            Cue::sunset(),
+            Cue::starry_sky(),
+        ];
+        for cue in cues.iter() {
+            let mut frame = [WideColor::black(); CHANNELS as usize];
+            for time_ms in (0..40_000).step_by(777) {
+                cue.render_wide_into(time_ms, &mut frame);
+                for channel in 0..CHANNELS {
+                    let wide = cue.current_wide_color(time_ms, channel);
+                    assert_eq!(frame[channel as usize], wide);
+                    // The 8 bit progress can be a step behind, which changes the rainbow by up to 4
+                    let narrow: [u8; 3] = cue.current_color(time_ms, channel).into();
+                    let wide: [u8; 3] = Color::from(wide).into();
+                    for (narrow, wide) in narrow.iter().zip(wide.iter()) {
+                        assert!((*narrow as i32 - *wide as i32).abs() <= 4, "{:?}", cue);
+                    }
+                }
+            }
+        }
+
+        // A slow fade has far more steps than with 8 bits
+        let cue: Cue = Cue {
+            duration_ms: NonZeroU32::new(60_000).unwrap(),
+            ramp_type: RampType::LinearRGB,
+            ramp_ratio: U0F8::MAX.into(),
+            start_color: Color::black(),
+            end_color: Color::new(0, 0, 8),
+            ..Default::default()
+        };
+        let mut steps = 0;
+        let mut previous = WideColor::black();
+        for time_ms in (0..60_000).step_by(10) {
+            let color = cue.current_wide_color(time_ms, 0);
+            if color != previous {
+                steps += 1;
+                previous = color;
+            }
+        }
+        assert!(steps > 1000);
+    }
+
+    #[test]
+    fn extreme_ramp_ratios() {
+        for ramp_ratio in [U0F8::MIN, U0F8::MAX].iter() {
+            let cue: Cue = Cue {
+                ramp_ratio: (*ramp_ratio).into(),
+                ..Cue::white_breathing()
+            };
+            for time_ms in 0..cue.duration_ms.get() {
+                for channel in 0..CHANNELS {
+                    cue.current_color(time_ms, channel);
+                    cue.current_wide_color(time_ms, channel);
+                }
+            }
+            // The end color is still reached
+            assert!((0..cue.duration_ms.get())
+                .any(|time_ms| cue.current_color(time_ms, 0) == Color::white()));
+        }
+    }
+
+    #[test]
+    fn checked_channels() {
+        let cue = Cue::<4>::rainbow();
+        for channel in 0..4 {
+            assert_eq!(
+                cue.try_current_color(100, channel),
+                Ok(cue.current_color(100, channel))
+            );
+            assert_eq!(
+                cue.try_progress(100, channel),
+                Ok(cue.progress(100, channel))
+            );
+        }
+        assert_eq!(
+            cue.try_current_color(100, 4),
+            Err(CueError::InvalidChannel(4))
+        );
+        assert_eq!(
+            cue.try_progress(100, 255),
+            Err(CueError::InvalidChannel(255))
+        );
+        // Axes count half LEDs
+        let symmetric = Cue::<4> {
+            symmetry: Some(Symmetry { axis: 7 }),
+            ..cue
+        };
+        assert_eq!(symmetric.validate(), Ok(()));
+        let invalid = Cue::<4> {
+            symmetry: Some(Symmetry { axis: 8 }),
+            ..symmetric
+        };
+        assert_eq!(invalid.validate(), Err(CueError::InvalidSymmetryAxis(8)));
+        // Rendering a Cue that wasn't validated still works
+        for time_ms in (0..3000).step_by(100) {
+            let frame = invalid.render_frame(time_ms);
+            assert_eq!(frame[3], invalid.current_color(time_ms, 3));
+        }
+    }
+
+    #[test]
+    fn phase_map() {
+        let uniform: Cue<8> = Cue {
+            time_divisor: NonZeroU8::new(4).unwrap(),
+            duration_ms: NonZeroU32::new(2560).unwrap(),
+            ..Cue::rainbow()
+        };
+        // A quarter of the duration apart, just like a time divisor of 4
+        let spaced = Cue {
+            phase_map: Some(PhaseMap::spaced(U0F8::from_bits(64).into())),
+            ..uniform.clone()
+        };
+        let scattered = Cue {
+            phase_map: Some(PhaseMap::random_scatter(3)),
+            ..uniform.clone()
+        };
+        for time_ms in (0..2560).step_by(10) {
+            for channel in 0..8 {
+                assert_eq!(
+                    spaced.progress(time_ms, channel),
+                    uniform.progress(time_ms, channel)
+                );
+                // Offsets are a fraction of the duration
+                let offset = scattered.phase_map.unwrap().offsets[channel as usize];
+                let offset_ms = offset.0.to_bits() as u32 * 10;
+                assert_eq!(
+                    scattered.progress(time_ms, channel),
+                    uniform.progress(time_ms + offset_ms, 7)
+                );
+            }
+        }
+
+        // Reversing negates the offsets
+        let reversed = Cue {
+            reverse: true,
+            ..scattered.clone()
+        };
+        let offset = scattered.phase_map.unwrap().offsets[2].0.to_bits() as u32;
+        assert_eq!(reversed.progress(offset * 10, 2), U0F8::MIN);
+        assert_eq!(
+            reversed.render_frame(123)[2],
+            reversed.current_color(123, 2)
+        );
+    }
+
+    #[test]
+    fn phase_map_presets() {
+        let plain: Cue<8> = Cue::rainbow();
+        let spaced = Cue {
+            phase_map: Some(PhaseMap::spaced(U0F8::from_bits(32).into())),
+            ..plain.clone()
+        };
+        // The first half is animated like a time divisor of 4, and mirrored onto the second half
+        let half = Cue {
+            time_divisor: NonZeroU8::new(4).unwrap(),
+            ..plain.clone()
+        };
+        let mirror = Cue {
+            phase_map: Some(PhaseMap::mirror()),
+            ..plain.clone()
+        };
+        for time_ms in (0..3000).step_by(7) {
+            for channel in 0..8 {
+                assert_eq!(
+                    spaced.progress(time_ms, channel),
+                    plain.progress(time_ms, channel)
+                );
+            }
+            for channel in 0..4 {
+                let progress = half.progress(time_ms, channel);
+                assert_eq!(mirror.progress(time_ms, channel), progress);
+                assert_eq!(mirror.progress(time_ms, 7 - channel), progress);
+            }
+        }
+    }
+
+    #[test]
+    fn symmetry() {
+        // Two comets starting at channel 0 and meeting at channel 6
+        let comets: Cue = Cue {
+            symmetry: Some(Symmetry { axis: 0 }),
+            ..Cue::rainbow()
+        };
+        let plain: Cue = Cue::rainbow();
+        for time_ms in (0..3000).step_by(50) {
+            for channel in 1..6 {
+                assert_eq!(
+                    comets.current_color(time_ms, channel),
+                    comets.current_color(time_ms, 12 - channel)
+                );
+            }
+            for channel in 0..=6 {
+                assert_eq!(
+                    comets.progress(time_ms, channel),
+                    plain.progress(time_ms, channel)
+                );
+            }
+            let frame = comets.render_frame(time_ms);
+            assert_eq!(frame[9], comets.current_color(time_ms, 9));
+        }
     }
 }
@@ -0,0 +1,108 @@
+use crate::color::Color;
+use fixed::types::U8F8;
+
+/// Reduces precise values to 8 bits by rounding up in some frames and down in
+/// others, so the average over consecutive frames is the precise value. This
+/// makes dark colors and slow fades much smoother than simply rounding.
+///
+/// The pattern only depends on the frame number, so it is deterministic and
+/// needs no state per LED. It repeats every 256 frames, and every aligned
+/// block of 2^k frames already matches the precise value to k bits.
+/// Neighbouring LEDs use shifted patterns, so they don't flicker in sync.
+/// # Examples
+/// ```
+/// use fixed::types::U8F8;
+/// use iris_lib::dither::TemporalDither;
+///
+/// let value = U8F8::from_num(2.25);
+/// let mut dither = TemporalDither::new(0);
+/// let mut frames = [0; 4];
+/// for frame in frames.iter_mut() {
+///     *frame = dither.dither(value, 0);
+///     dither.next_frame();
+/// }
+/// assert_eq!(frames, [2, 2, 2, 3]);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct TemporalDither {
+    frame: u8,
+}
+
+impl TemporalDither {
+    /// Start at the given frame. Only the lowest 8 bits matter, as the
+    /// pattern repeats every 256 frames.
+    pub fn new(frame: u32) -> TemporalDither {
+        TemporalDither { frame: frame as u8 }
+    }
+
+    pub fn next_frame(&mut self) {
+        self.frame = self.frame.wrapping_add(1);
+    }
+
+    /// The 8 bit value for the current frame of a single LED color.
+    /// `position` selects the shift of the pattern, e.g. the index of the LED.
+    pub fn dither(&self, value: U8F8, position: u16) -> u8 {
+        // Any odd step visits all shifts before repeating
+        let shift = (position as u32 * 0x9D) as u8;
+        // Bit reversal spreads the thresholds evenly over time
+        let threshold = self.frame.wrapping_add(shift).reverse_bits();
+        ((value.to_bits() as u32 + threshold as u32) >> 8).min(u8::MAX as u32) as u8
+    }
+
+    /// The color for the current frame of the LED at `channel`
+    pub fn dither_color(&self, color: [U8F8; 3], channel: u8) -> Color {
+        let position = channel as u16 * 3;
+        Color::new(
+            self.dither(color[0], position),
+            self.dither(color[1], position + 1),
+            self.dither(color[2], position + 2),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dither::*;
+
+    #[test]
+    fn averages_to_precise_value() {
+        for bits in (0..=u16::MAX)
+            .step_by(97)
+            .chain([0xFF00, u16::MAX].iter().copied())
+        {
+            let value = U8F8::from_bits(bits);
+            for position in [0, 1, 35, 1000].iter() {
+                let mut dither = TemporalDither::new(*position as u32 * 7);
+                let mut sum = 0;
+                for _ in 0..256 {
+                    sum += dither.dither(value, *position) as u32;
+                    dither.next_frame();
+                }
+                // Values above 255 can't be reached
+                assert_eq!(sum, (bits as u32).min(0xFF00), "{}", value);
+            }
+        }
+    }
+
+    #[test]
+    fn whole_values_are_unchanged() {
+        for value in 0..=u8::MAX {
+            for frame in 0..256 {
+                let dither = TemporalDither::new(frame);
+                let color = [U8F8::from_num(value); 3];
+                assert_eq!(
+                    dither.dither_color(color, 5),
+                    Color::new(value, value, value)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn neighbours_differ() {
+        let dither = TemporalDither::new(0);
+        let color = dither.dither_color([U8F8::from_num(0.5); 3], 0);
+        assert_ne!(color, Color::black());
+        assert_ne!(color, Color::new(1, 1, 1));
+    }
+}
@@ -0,0 +1,294 @@
+use crate::cue::Fraction;
+use fixed::types::{U0F16, U0F8};
+use serde::{Deserialize, Serialize};
+
+/// Curve that is applied to the mixing factor of a Cue, so transitions can
+/// accelerate and decelerate instead of changing at a constant rate.
+/// All curves map 0 to 0 and 1 ([`U0F8::MAX`]) to 1.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, Default)]
+pub enum Easing {
+    /// Constant rate of change
+    #[default]
+    Linear,
+    /// Start slowly, following a sine wave
+    InSine,
+    /// End slowly, following a sine wave
+    OutSine,
+    /// Start and end slowly, following a sine wave
+    InOutSine,
+    /// Start slowly, following a cubic curve. More pronounced than [`Easing::InSine`]
+    InCubic,
+    /// End slowly, following a cubic curve. More pronounced than [`Easing::OutSine`]
+    OutCubic,
+    /// Start and end slowly, following a cubic curve
+    InOutCubic,
+    /// Custom curve defined by two control points, just like the CSS
+    /// `cubic-bezier()` timing function. The start and end points are fixed
+    /// at (0, 0) and (1, 1). Unlike CSS, y can't leave the range from 0 to 1.
+    CubicBezier {
+        x1: Fraction,
+        y1: Fraction,
+        x2: Fraction,
+        y2: Fraction,
+    },
+}
+
+/// sin(x) for 33 evenly spaced x between 0 and π/2, scaled to u16::MAX
+const QUARTER_SINE: [u16; 33] = [
+    0, 3216, 6424, 9616, 12785, 15924, 19024, 22078, 25079, 28020, 30893, 33692, 36409, 39039,
+    41575, 44011, 46340, 48558, 50659, 52638, 54490, 56211, 57797, 59243, 60546, 61704, 62713,
+    63571, 64276, 64826, 65219, 65456, 65535,
+];
+
+/// Resolution of the parameter when solving [`Easing::CubicBezier`]
+const BEZIER_STEPS: u64 = 4096;
+
+impl Easing {
+    /// Apply the curve to a factor between 0 and 1
+    /// # Examples
+    /// ```
+    /// use iris_lib::easing::Easing;
+    /// use fixed::types::U0F8;
+    /// use fixed_macro::types::U0F8;
+    ///
+    /// assert_eq!(Easing::Linear.apply(U0F8!(0.25)), U0F8!(0.25));
+    /// assert_eq!(Easing::InCubic.apply(U0F8!(0.5)), U0F8::from_bits(32));
+    /// assert_eq!(Easing::OutSine.apply(U0F8::MAX), U0F8::MAX);
+    /// ```
+    pub fn apply(&self, factor: U0F8) -> U0F8 {
+        let t = factor.to_bits();
+        let eased = match *self {
+            Easing::Linear => t,
+            Easing::InSine => u8::MAX - quarter_sine(u8::MAX - t),
+            Easing::OutSine => quarter_sine(t),
+            // Actual formula: (1 - cos(π * t)) / 2
+            Easing::InOutSine => {
+                if t < 128 {
+                    (u8::MAX - quarter_sine(u8::MAX - 2 * t)).div_ceil(2)
+                } else {
+                    (u8::MAX as u16 + quarter_sine(t - (u8::MAX - t)) as u16).div_ceil(2) as u8
+                }
+            }
+            Easing::InCubic => cube(t),
+            Easing::OutCubic => u8::MAX - cube(u8::MAX - t),
+            // Actual formula: 4 * t^3, or 1 - 4 * (1 - t)^3 for the second half
+            Easing::InOutCubic => {
+                if t < 128 {
+                    quadruple_cube(t)
+                } else {
+                    u8::MAX - quadruple_cube(u8::MAX - t)
+                }
+            }
+            Easing::CubicBezier { x1, y1, x2, y2 } => cubic_bezier(
+                t,
+                [
+                    x1.0.to_bits(),
+                    y1.0.to_bits(),
+                    x2.0.to_bits(),
+                    y2.0.to_bits(),
+                ],
+            ),
+        };
+        U0F8::from_bits(eased)
+    }
+
+    /// Like [`Easing::apply`], but with 16 bits. The curves are interpolated
+    /// linearly between the 256 points of the 8 bit version.
+    /// # Examples
+    /// ```
+    /// use iris_lib::easing::Easing;
+    /// use fixed::types::U0F16;
+    /// use fixed_macro::types::U0F16;
+    ///
+    /// assert_eq!(Easing::Linear.apply_wide(U0F16!(0.3)), U0F16!(0.3));
+    /// assert_eq!(Easing::InCubic.apply_wide(U0F16::MAX), U0F16::MAX);
+    /// ```
+    pub fn apply_wide(&self, factor: U0F16) -> U0F16 {
+        if *self == Easing::Linear {
+            return factor;
+        }
+        // u16::MAX = 257 * u8::MAX, so every step of the 8 bit factor is 257 wide
+        let step = (factor.to_bits() / 257) as u8;
+        let remainder = (factor.to_bits() % 257) as i32;
+        let lower = self.apply(U0F8::from_bits(step)).to_bits() as i32;
+        let upper = self
+            .apply(U0F8::from_bits(step.saturating_add(1)))
+            .to_bits() as i32;
+        U0F16::from_bits((lower * 257 + (upper - lower) * remainder) as u16)
+    }
+}
+
+/// sin(x * π/2) for x between 0 and 1, interpolated from [`QUARTER_SINE`]
+fn quarter_sine(x: u8) -> u8 {
+    let segments = (QUARTER_SINE.len() - 1) as u32;
+    // Position inside the table with 8 fractional bits
+    let position = (x as u32 * segments * 256) / u8::MAX as u32;
+    let index = (position >> 8) as usize;
+    let fraction = position & 0xFF;
+
+    let lower = QUARTER_SINE[index] as u32;
+    let upper = QUARTER_SINE[(index + 1).min(QUARTER_SINE.len() - 1)] as u32;
+    let value = lower + (((upper - lower) * fraction) >> 8);
+
+    // Scale u16::MAX down to u8::MAX with rounding
+    ((value * u8::MAX as u32 + u16::MAX as u32 / 2) / u16::MAX as u32) as u8
+}
+
+/// x^3 for x between 0 and 1
+fn cube(x: u8) -> u8 {
+    let x = x as u32;
+    let max = u8::MAX as u32;
+    // x^3 ≤ 0xFD02FF, so this can't overflow
+    ((x * x * x + max * max / 2) / (max * max)) as u8
+}
+
+/// 4 * x^3 for x between 0 and 0.5
+fn quadruple_cube(x: u8) -> u8 {
+    let x = x as u32;
+    let max = u8::MAX as u32;
+    ((4 * x * x * x + max * max / 2) / (max * max)).min(max) as u8
+}
+
+/// Evaluate one coordinate of the bezier curve at s / BEZIER_STEPS.
+/// The result is scaled by BEZIER_STEPS^3, so it keeps full precision.
+/// Actual formula: 3(1 - s)^2 * s * p1 + 3(1 - s) * s^2 * p2 + s^3
+fn bezier_coordinate(s: u64, p1: u8, p2: u8) -> u64 {
+    let inverse = BEZIER_STEPS - s;
+    3 * inverse * inverse * s * p1 as u64
+        + 3 * inverse * s * s * p2 as u64
+        + s * s * s * u8::MAX as u64
+}
+
+/// Find the y coordinate of the curve at the given x coordinate.
+/// x is monotonic in s because both control points lie between 0 and 1,
+/// so a binary search for s is sufficient.
+fn cubic_bezier(x: u8, [x1, y1, x2, y2]: [u8; 4]) -> u8 {
+    let target = x as u64 * BEZIER_STEPS * BEZIER_STEPS * BEZIER_STEPS;
+
+    // Find the smallest s where the curve reaches x
+    let (mut low, mut high) = (0, BEZIER_STEPS);
+    while low < high {
+        let mid = (low + high) / 2;
+        if bezier_coordinate(mid, x1, x2) < target {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+
+    let denominator = BEZIER_STEPS * BEZIER_STEPS * BEZIER_STEPS;
+    ((bezier_coordinate(low, y1, y2) + denominator / 2) / denominator) as u8
+}
+
+#[cfg(test)]
+mod test {
+    use crate::easing::*;
+    use core::f64::consts::PI;
+    extern crate std;
+
+    const ALL: [Easing; 7] = [
+        Easing::Linear,
+        Easing::InSine,
+        Easing::OutSine,
+        Easing::InOutSine,
+        Easing::InCubic,
+        Easing::OutCubic,
+        Easing::InOutCubic,
+    ];
+
+    fn apply(easing: Easing, t: u8) -> u8 {
+        easing.apply(U0F8::from_bits(t)).to_bits()
+    }
+
+    #[test]
+    fn endpoints() {
+        for easing in ALL.iter() {
+            assert_eq!(apply(*easing, 0), 0, "{:?}", easing);
+            assert_eq!(apply(*easing, u8::MAX), u8::MAX, "{:?}", easing);
+        }
+    }
+
+    #[test]
+    fn monotonic() {
+        for easing in ALL.iter() {
+            for t in 0..u8::MAX {
+                assert!(apply(*easing, t) <= apply(*easing, t + 1), "{:?}", easing);
+            }
+        }
+    }
+
+    #[test]
+    fn wide_matches_steps() {
+        for easing in ALL.iter() {
+            for t in 0..=u8::MAX {
+                let wide = easing.apply_wide(U0F16::from_bits(t as u16 * 257));
+                assert_eq!(wide.to_bits(), apply(*easing, t) as u16 * 257);
+            }
+            for bits in 0..u16::MAX {
+                let current = easing.apply_wide(U0F16::from_bits(bits));
+                assert!(current <= easing.apply_wide(U0F16::from_bits(bits + 1)));
+            }
+        }
+    }
+
+    #[test]
+    fn matches_float_curves() {
+        type Curve = fn(f64) -> f64;
+        let curves: [(Easing, Curve); 6] = [
+            (Easing::InSine, |t| 1.0 - (t * PI / 2.0).cos()),
+            (Easing::OutSine, |t| (t * PI / 2.0).sin()),
+            (Easing::InOutSine, |t| (1.0 - (t * PI).cos()) / 2.0),
+            (Easing::InCubic, |t| t * t * t),
+            (Easing::OutCubic, |t| {
+                1.0 - (1.0 - t) * (1.0 - t) * (1.0 - t)
+            }),
+            (Easing::InOutCubic, |t| {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - 4.0 * (1.0 - t) * (1.0 - t) * (1.0 - t)
+                }
+            }),
+        ];
+        for (easing, curve) in curves.iter() {
+            for t in 0..=u8::MAX {
+                let expected = curve(t as f64 / 255.0) * 255.0;
+                let actual = apply(*easing, t) as f64;
+                assert!(
+                    (expected - actual).abs() <= 1.0,
+                    "{:?}({}) = {}, expected {}",
+                    easing,
+                    t,
+                    actual,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn cubic_bezier() {
+        // Control points on the diagonal produce a straight line
+        let linear = Easing::CubicBezier {
+            x1: 0.25.into(),
+            y1: 0.25.into(),
+            x2: 0.75.into(),
+            y2: 0.75.into(),
+        };
+        for t in 0..=u8::MAX {
+            assert!((apply(linear, t) as i16 - t as i16).abs() <= 1);
+        }
+
+        // CSS `ease-in-out`, evaluated at x = 0.25 is y ≈ 0.129
+        let ease_in_out = Easing::CubicBezier {
+            x1: 0.42.into(),
+            y1: 0.0.into(),
+            x2: 0.58.into(),
+            y2: 1.0.into(),
+        };
+        assert_eq!(apply(ease_in_out, 0), 0);
+        assert_eq!(apply(ease_in_out, 64), 33);
+        assert_eq!(apply(ease_in_out, 128), 128);
+        assert_eq!(apply(ease_in_out, u8::MAX), u8::MAX);
+    }
+}
@@ -0,0 +1,942 @@
+//! Compact binary encoding of Cues and Schedules, small enough to store them
+//! in the EEPROM of a microcontroller. Unlike serde's formats, it needs no allocation and no
+//! field names, so a typical Cue takes less than 30 bytes.
+//!
+//! Every encoding starts with a header that stays the same in every version:
+//! the format version (1 byte) and the length of the payload (2 bytes).
+//! The payload is followed by a CRC-16 of the header and the payload.
+//! All numbers are little endian.
+//!
+//! The payload of a Cue consists of, in this order:
+//! - Number of channels N (1 byte), must match the decoding Cue
+//! - Flags (1 byte) for `reverse` and which optional fields are present
+//! - `channels` as a bit mask, channel 0 in the lowest bit (N / 8 bytes, rounded up)
+//! - `time_divisor` (1 byte)
+//! - `phase_map` if present (N bytes)
+//! - `symmetry` if present (1 byte)
+//! - `duration_ms` (4 bytes)
+//! - `ramp_type` (1 byte), followed by its easing and hue arc, if any
+//! - `ramp_ratio` (1 byte)
+//! - `start_color` and `end_color` (3 bytes each)
+//! - `gradient` if present: number of stops (1 byte), then position and color of each (4 bytes each)
+//! - `twinkle` if present: seed (4 bytes), color (3 bytes), density (1 byte), decay_ms (2 bytes)
+//! - `playback` (1 byte), followed by the number of periods for [`Playback::Repeat`] (2 bytes)
+//!
+//! The payload of a [`Schedule`] is the number of layers (1 byte), followed by
+//! each layer from the highest to the lowest priority:
+//! - `priority` (1 byte)
+//! - `blend_mode` (1 byte)
+//! - `opacity` (1 byte)
+//! - `cue` with the same fields as the payload of a Cue
+
+use crate::color::{BlendMode, Color, HueArc};
+use crate::cue::{Cue, CueError, Fraction, Playback, RampType};
+use crate::easing::Easing;
+use crate::gradient::{Gradient, MAX_STOPS};
+use crate::phase::{PhaseMap, Symmetry};
+use crate::schedule::{Layer, Schedule, MAX_LAYERS};
+use crate::twinkle::Twinkle;
+use core::fmt;
+use core::num::{NonZeroU16, NonZeroU32, NonZeroU8};
+use fixed::types::U0F8;
+
+/// Version of the encoding written by [`Cue::encode_into`] and [`Schedule::encode_into`]
+pub const FORMAT_VERSION: u8 = 1;
+
+/// Length of the longest possible encoded Cue, which has 255 channels and
+/// uses every field. Enough for a buffer that can hold any Cue.
+pub const MAX_ENCODED_LEN: usize = 360;
+
+/// Format version and payload length
+const HEADER_LEN: usize = 3;
+const CRC_LEN: usize = 2;
+
+const REVERSE: u8 = 1 << 0;
+const HAS_PHASE_MAP: u8 = 1 << 1;
+const HAS_SYMMETRY: u8 = 1 << 2;
+const HAS_GRADIENT: u8 = 1 << 3;
+const HAS_TWINKLE: u8 = 1 << 4;
+const ALL_FLAGS: u8 = REVERSE | HAS_PHASE_MAP | HAS_SYMMETRY | HAS_GRADIENT | HAS_TWINKLE;
+
+/// The buffer passed to [`Cue::encode_into`] or [`Schedule::encode_into`]
+/// can't hold the encoding
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BufferTooSmall {
+    /// Length of the encoding in bytes
+    pub needed: usize,
+}
+
+/// Reasons why [`Cue::decode`] or [`Schedule::decode`] rejected the data
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The data ends before the encoding does
+    UnexpectedEnd,
+    /// The data was written in a format version this crate doesn't know
+    UnsupportedVersion(u8),
+    /// The Cue was encoded for a different number of LEDs
+    ChannelCount(u8),
+    /// The data is corrupted
+    ChecksumMismatch,
+    /// A field has a value that doesn't exist, e.g. an unknown [`RampType`]
+    /// or more than [`MAX_LAYERS`] layers
+    InvalidValue,
+    /// The decoded Cue is invalid
+    InvalidCue(CueError),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEnd => write!(f, "data ends unexpectedly"),
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "format version {} is not supported", version)
+            }
+            DecodeError::ChannelCount(channels) => {
+                write!(f, "Cue was encoded for {} channels", channels)
+            }
+            DecodeError::ChecksumMismatch => write!(f, "checksum doesn't match"),
+            DecodeError::InvalidValue => write!(f, "field has an invalid value"),
+            DecodeError::InvalidCue(error) => write!(f, "invalid Cue: {}", error),
+        }
+    }
+}
+
+impl From<CueError> for DecodeError {
+    fn from(error: CueError) -> DecodeError {
+        DecodeError::InvalidCue(error)
+    }
+}
+
+impl<const N: usize> Cue<N> {
+    /// Number of bytes written by [`Cue::encode_into`]
+    pub fn encoded_len(&self) -> usize {
+        encoded_len(|writer| self.write_payload(writer))
+    }
+
+    /// Write the binary encoding described in [`crate::encoding`] to the
+    /// start of the buffer and return its length in bytes
+    /// # Examples
+    /// ```
+    /// use iris_lib::cue::Cue;
+    ///
+    /// let cue: Cue = Cue::rainbow();
+    /// let mut buffer = [0; 64];
+    /// let len = cue.encode_into(&mut buffer).unwrap();
+    /// assert_eq!(len, cue.encoded_len());
+    /// assert_eq!(Cue::decode(&buffer[..len]), Ok(cue));
+    /// ```
+    pub fn encode_into(&self, buffer: &mut [u8]) -> Result<usize, BufferTooSmall> {
+        encode_into(buffer, |writer| self.write_payload(writer))
+    }
+
+    /// Read a Cue written by [`Cue::encode_into`]. Data after the end of the
+    /// encoded Cue is ignored, so a whole memory region can be passed.
+    /// The decoded Cue is checked with [`Cue::validate`].
+    pub fn decode(bytes: &[u8]) -> Result<Cue<N>, DecodeError> {
+        decode(bytes, Cue::read_payload)
+    }
+
+    fn write_payload(&self, writer: &mut Writer) {
+        let flag = |set: bool, flag: u8| if set { flag } else { 0 };
+        writer.u8(N as u8);
+        writer.u8(flag(self.reverse, REVERSE)
+            | flag(self.phase_map.is_some(), HAS_PHASE_MAP)
+            | flag(self.symmetry.is_some(), HAS_SYMMETRY)
+            | flag(self.gradient.is_some(), HAS_GRADIENT)
+            | flag(self.twinkle.is_some(), HAS_TWINKLE));
+        for chunk in self.channels.chunks(8) {
+            writer.u8(chunk
+                .iter()
+                .rev()
+                .fold(0, |byte, set| byte << 1 | *set as u8));
+        }
+        writer.u8(self.time_divisor.get());
+        if let Some(phase_map) = &self.phase_map {
+            for offset in phase_map.offsets.iter() {
+                writer.fraction(*offset);
+            }
+        }
+        if let Some(symmetry) = self.symmetry {
+            writer.u8(symmetry.axis);
+        }
+        writer.u32(self.duration_ms.get());
+        writer.ramp_type(self.ramp_type);
+        writer.fraction(self.ramp_ratio);
+        writer.color(self.start_color);
+        writer.color(self.end_color);
+        if let Some(gradient) = &self.gradient {
+            writer.u8(gradient.len() as u8);
+            for stop in gradient.stops() {
+                writer.fraction(stop.position);
+                writer.color(stop.color);
+            }
+        }
+        if let Some(twinkle) = &self.twinkle {
+            writer.u32(twinkle.seed);
+            writer.color(twinkle.color);
+            writer.fraction(twinkle.density);
+            writer.u16(twinkle.decay_ms.get());
+        }
+        writer.playback(self.playback);
+    }
+
+    /// Also validates the Cue, see [`Cue::validate`]
+    fn read_payload(reader: &mut Reader) -> Result<Cue<N>, DecodeError> {
+        let channels = reader.u8()?;
+        if channels as usize != N {
+            return Err(DecodeError::ChannelCount(channels));
+        }
+        let flags = reader.u8()?;
+        if flags & !ALL_FLAGS != 0 {
+            return Err(DecodeError::InvalidValue);
+        }
+
+        let mut cue = Cue {
+            reverse: flags & REVERSE != 0,
+            ..Cue::default()
+        };
+        for chunk in cue.channels.chunks_mut(8) {
+            let byte = reader.u8()?;
+            // Bits of channels that don't exist have to be 0
+            if (byte as u16) >> chunk.len() != 0 {
+                return Err(DecodeError::InvalidValue);
+            }
+            for (bit, channel) in chunk.iter_mut().enumerate() {
+                *channel = byte & (1 << bit) != 0;
+            }
+        }
+        cue.time_divisor = NonZeroU8::new(reader.u8()?).ok_or(CueError::ZeroTimeDivisor)?;
+        if flags & HAS_PHASE_MAP != 0 {
+            let mut offsets = [Fraction::default(); N];
+            for offset in offsets.iter_mut() {
+                *offset = reader.fraction()?;
+            }
+            cue.phase_map = Some(PhaseMap::new(offsets));
+        }
+        if flags & HAS_SYMMETRY != 0 {
+            cue.symmetry = Some(Symmetry { axis: reader.u8()? });
+        }
+        cue.duration_ms = NonZeroU32::new(reader.u32()?).ok_or(CueError::ZeroDuration)?;
+        cue.ramp_type = reader.ramp_type()?;
+        cue.ramp_ratio = reader.fraction()?;
+        cue.start_color = reader.color()?;
+        cue.end_color = reader.color()?;
+        if flags & HAS_GRADIENT != 0 {
+            let len = reader.u8()? as usize;
+            if len > MAX_STOPS {
+                return Err(DecodeError::InvalidValue);
+            }
+            let mut gradient = Gradient::new();
+            for _ in 0..len {
+                let position = reader.fraction()?;
+                let color = reader.color()?;
+                gradient
+                    .add(position, color)
+                    .map_err(|_| DecodeError::InvalidValue)?;
+            }
+            cue.gradient = Some(gradient);
+        }
+        if flags & HAS_TWINKLE != 0 {
+            cue.twinkle = Some(Twinkle {
+                seed: reader.u32()?,
+                color: reader.color()?,
+                density: reader.fraction()?,
+                decay_ms: NonZeroU16::new(reader.u16()?).ok_or(DecodeError::InvalidValue)?,
+            });
+        }
+        cue.playback = reader.playback()?;
+        cue.validate()?;
+        Ok(cue)
+    }
+}
+
+impl<const N: usize> Schedule<N> {
+    /// Number of bytes written by [`Schedule::encode_into`]
+    pub fn encoded_len(&self) -> usize {
+        encoded_len(|writer| self.write_payload(writer))
+    }
+
+    /// Write the binary encoding described in [`crate::encoding`] to the
+    /// start of the buffer and return its length in bytes
+    /// # Examples
+    /// ```
+    /// use iris_lib::cue::Cue;
+    /// use iris_lib::schedule::Schedule;
+    ///
+    /// let mut schedule: Schedule = Schedule::new();
+    /// schedule.add(0, Cue::rainbow()).unwrap();
+    /// schedule.add(1, Cue::starry_sky()).unwrap();
+    /// let mut buffer = [0; 128];
+    /// let len = schedule.encode_into(&mut buffer).unwrap();
+    /// assert_eq!(len, schedule.encoded_len());
+    /// assert_eq!(Schedule::decode(&buffer[..len]), Ok(schedule));
+    /// ```
+    pub fn encode_into(&self, buffer: &mut [u8]) -> Result<usize, BufferTooSmall> {
+        encode_into(buffer, |writer| self.write_payload(writer))
+    }
+
+    /// Read a Schedule written by [`Schedule::encode_into`]. Like
+    /// [`Cue::decode`], trailing data is ignored and every Cue is validated.
+    pub fn decode(bytes: &[u8]) -> Result<Schedule<N>, DecodeError> {
+        decode(bytes, Schedule::read_payload)
+    }
+
+    fn write_payload(&self, writer: &mut Writer) {
+        writer.u8(self.len() as u8);
+        for layer in self.layers() {
+            writer.u8(layer.priority);
+            writer.blend_mode(layer.blend_mode);
+            writer.fraction(layer.opacity);
+            layer.cue.write_payload(writer);
+        }
+    }
+
+    fn read_payload(reader: &mut Reader) -> Result<Schedule<N>, DecodeError> {
+        let len = reader.u8()? as usize;
+        if len > MAX_LAYERS {
+            return Err(DecodeError::InvalidValue);
+        }
+        let mut schedule = Schedule::new();
+        for _ in 0..len {
+            let layer = Layer {
+                priority: reader.u8()?,
+                blend_mode: reader.blend_mode()?,
+                opacity: reader.fraction()?,
+                cue: Cue::read_payload(reader)?,
+            };
+            // Never full, as there are at most MAX_LAYERS layers
+            let _ = schedule.add_layer(layer);
+        }
+        Ok(schedule)
+    }
+}
+
+/// Length of the encoding with the payload written by `write_payload`
+fn encoded_len(write_payload: impl FnOnce(&mut Writer)) -> usize {
+    let mut writer = Writer::new(&mut []);
+    write_payload(&mut writer);
+    HEADER_LEN + writer.position + CRC_LEN
+}
+
+/// Write the header, the payload and the CRC
+fn encode_into(
+    buffer: &mut [u8],
+    write_payload: impl Fn(&mut Writer),
+) -> Result<usize, BufferTooSmall> {
+    let needed = encoded_len(&write_payload);
+    if buffer.len() < needed {
+        return Err(BufferTooSmall { needed });
+    }
+    let (header, rest) = buffer.split_at_mut(HEADER_LEN);
+    let mut writer = Writer::new(rest);
+    write_payload(&mut writer);
+    let payload_len = writer.position;
+
+    header[0] = FORMAT_VERSION;
+    header[1..].copy_from_slice(&(payload_len as u16).to_le_bytes());
+    let end = HEADER_LEN + payload_len;
+    let crc = crc16(&buffer[..end]);
+    buffer[end..needed].copy_from_slice(&crc.to_le_bytes());
+    Ok(needed)
+}
+
+/// Check the header and the CRC, then read the payload, which has to end
+/// exactly where the header says
+fn decode<T>(
+    bytes: &[u8],
+    read_payload: impl FnOnce(&mut Reader) -> Result<T, DecodeError>,
+) -> Result<T, DecodeError> {
+    let mut header = Reader::new(bytes);
+    let version = header.u8()?;
+    if version != FORMAT_VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+    let end = HEADER_LEN + header.u16()? as usize;
+    let crc = bytes
+        .get(end..end + CRC_LEN)
+        .ok_or(DecodeError::UnexpectedEnd)?;
+    if crc16(&bytes[..end]).to_le_bytes() != crc {
+        return Err(DecodeError::ChecksumMismatch);
+    }
+
+    let mut reader = Reader::new(&bytes[HEADER_LEN..end]);
+    let decoded = read_payload(&mut reader)?;
+    if reader.position != end - HEADER_LEN {
+        return Err(DecodeError::InvalidValue);
+    }
+    Ok(decoded)
+}
+
+/// Writes values one after another. Values that don't fit into the buffer
+/// are skipped, but still counted, so this can also measure the length.
+struct Writer<'a> {
+    buffer: &'a mut [u8],
+    position: usize,
+}
+
+impl Writer<'_> {
+    fn new(buffer: &mut [u8]) -> Writer<'_> {
+        Writer {
+            buffer,
+            position: 0,
+        }
+    }
+
+    fn u8(&mut self, value: u8) {
+        if let Some(byte) = self.buffer.get_mut(self.position) {
+            *byte = value;
+        }
+        self.position += 1;
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.u8(*byte);
+        }
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn fraction(&mut self, value: Fraction) {
+        self.u8(value.0.to_bits());
+    }
+
+    fn color(&mut self, color: Color) {
+        let components: [u8; 3] = color.into();
+        self.bytes(&components);
+    }
+
+    fn hue_arc(&mut self, hue_arc: HueArc) {
+        self.u8(match hue_arc {
+            HueArc::Shortest => 0,
+            HueArc::Longest => 1,
+        });
+    }
+
+    fn easing(&mut self, easing: Easing) {
+        match easing {
+            Easing::Linear => self.u8(0),
+            Easing::InSine => self.u8(1),
+            Easing::OutSine => self.u8(2),
+            Easing::InOutSine => self.u8(3),
+            Easing::InCubic => self.u8(4),
+            Easing::OutCubic => self.u8(5),
+            Easing::InOutCubic => self.u8(6),
+            Easing::CubicBezier { x1, y1, x2, y2 } => {
+                self.u8(7);
+                for value in [x1, y1, x2, y2].iter() {
+                    self.fraction(*value);
+                }
+            }
+        }
+    }
+
+    fn ramp_type(&mut self, ramp_type: RampType) {
+        match ramp_type {
+            RampType::Jump => self.u8(0),
+            RampType::LinearRGB => self.u8(1),
+            RampType::LinearHSL { hue_arc } => {
+                self.u8(2);
+                self.hue_arc(hue_arc);
+            }
+            RampType::EasedRGB { easing } => {
+                self.u8(3);
+                self.easing(easing);
+            }
+            RampType::EasedHSL { easing, hue_arc } => {
+                self.u8(4);
+                self.easing(easing);
+                self.hue_arc(hue_arc);
+            }
+            RampType::LinearOklab => self.u8(5),
+            RampType::LinearOklch { hue_arc } => {
+                self.u8(6);
+                self.hue_arc(hue_arc);
+            }
+            RampType::EasedOklab { easing } => {
+                self.u8(7);
+                self.easing(easing);
+            }
+            RampType::EasedOklch { easing, hue_arc } => {
+                self.u8(8);
+                self.easing(easing);
+                self.hue_arc(hue_arc);
+            }
+        }
+    }
+
+    fn blend_mode(&mut self, blend_mode: BlendMode) {
+        self.u8(match blend_mode {
+            BlendMode::Replace => 0,
+            BlendMode::Alpha => 1,
+            BlendMode::Add => 2,
+            BlendMode::Multiply => 3,
+            BlendMode::Screen => 4,
+            BlendMode::Lighten => 5,
+        });
+    }
+
+    fn playback(&mut self, playback: Playback) {
+        match playback {
+            Playback::Loop => self.u8(0),
+            Playback::PingPong => self.u8(1),
+            Playback::Once => self.u8(2),
+            Playback::Repeat(periods) => {
+                self.u8(3);
+                self.u16(periods.get());
+            }
+        }
+    }
+}
+
+/// Reads values one after another, the counterpart of [`Writer`]
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn new(bytes: &[u8]) -> Reader<'_> {
+        Reader { bytes, position: 0 }
+    }
+
+    fn u8(&mut self) -> Result<u8, DecodeError> {
+        let byte = self
+            .bytes
+            .get(self.position)
+            .ok_or(DecodeError::UnexpectedEnd)?;
+        self.position += 1;
+        Ok(*byte)
+    }
+
+    fn u16(&mut self) -> Result<u16, DecodeError> {
+        Ok(u16::from_le_bytes([self.u8()?, self.u8()?]))
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_le_bytes([
+            self.u8()?,
+            self.u8()?,
+            self.u8()?,
+            self.u8()?,
+        ]))
+    }
+
+    fn fraction(&mut self) -> Result<Fraction, DecodeError> {
+        Ok(U0F8::from_bits(self.u8()?).into())
+    }
+
+    fn color(&mut self) -> Result<Color, DecodeError> {
+        Ok(Color::new(self.u8()?, self.u8()?, self.u8()?))
+    }
+
+    fn hue_arc(&mut self) -> Result<HueArc, DecodeError> {
+        match self.u8()? {
+            0 => Ok(HueArc::Shortest),
+            1 => Ok(HueArc::Longest),
+            _ => Err(DecodeError::InvalidValue),
+        }
+    }
+
+    fn easing(&mut self) -> Result<Easing, DecodeError> {
+        Ok(match self.u8()? {
+            0 => Easing::Linear,
+            1 => Easing::InSine,
+            2 => Easing::OutSine,
+            3 => Easing::InOutSine,
+            4 => Easing::InCubic,
+            5 => Easing::OutCubic,
+            6 => Easing::InOutCubic,
+            7 => Easing::CubicBezier {
+                x1: self.fraction()?,
+                y1: self.fraction()?,
+                x2: self.fraction()?,
+                y2: self.fraction()?,
+            },
+            _ => return Err(DecodeError::InvalidValue),
+        })
+    }
+
+    fn ramp_type(&mut self) -> Result<RampType, DecodeError> {
+        Ok(match self.u8()? {
+            0 => RampType::Jump,
+            1 => RampType::LinearRGB,
+            2 => RampType::LinearHSL {
+                hue_arc: self.hue_arc()?,
+            },
+            3 => RampType::EasedRGB {
+                easing: self.easing()?,
+            },
+            4 => RampType::EasedHSL {
+                easing: self.easing()?,
+                hue_arc: self.hue_arc()?,
+            },
+            5 => RampType::LinearOklab,
+            6 => RampType::LinearOklch {
+                hue_arc: self.hue_arc()?,
+            },
+            7 => RampType::EasedOklab {
+                easing: self.easing()?,
+            },
+            8 => RampType::EasedOklch {
+                easing: self.easing()?,
+                hue_arc: self.hue_arc()?,
+            },
+            _ => return Err(DecodeError::InvalidValue),
+        })
+    }
+
+    fn blend_mode(&mut self) -> Result<BlendMode, DecodeError> {
+        Ok(match self.u8()? {
+            0 => BlendMode::Replace,
+            1 => BlendMode::Alpha,
+            2 => BlendMode::Add,
+            3 => BlendMode::Multiply,
+            4 => BlendMode::Screen,
+            5 => BlendMode::Lighten,
+            _ => return Err(DecodeError::InvalidValue),
+        })
+    }
+
+    fn playback(&mut self) -> Result<Playback, DecodeError> {
+        Ok(match self.u8()? {
+            0 => Playback::Loop,
+            1 => Playback::PingPong,
+            2 => Playback::Once,
+            3 => Playback::Repeat(NonZeroU16::new(self.u16()?).ok_or(CueError::ZeroRepeats)?),
+            _ => return Err(DecodeError::InvalidValue),
+        })
+    }
+}
+
+/// CRC-16/CCITT-FALSE: polynomial 0x1021, initial value 0xFFFF.
+/// Calculated bit by bit, as a lookup table would take 512 bytes of flash.
+pub(crate) fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for byte in bytes {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod test {
+    use crate::encoding::*;
+
+    fn encode<const N: usize>(cue: &Cue<N>) -> ([u8; 128], usize) {
+        let mut buffer = [0; 128];
+        let len = cue.encode_into(&mut buffer).unwrap();
+        (buffer, len)
+    }
+
+    /// A Cue using every optional field
+    fn full_cue() -> Cue<4> {
+        let mut gradient = Gradient::new();
+        gradient.add(0.0.into(), Color::new(1, 2, 3)).unwrap();
+        gradient.add(1.0.into(), Color::new(4, 5, 6)).unwrap();
+        Cue {
+            channels: [true, false, true, true],
+            reverse: true,
+            time_divisor: NonZeroU8::new(2).unwrap(),
+            phase_map: Some(PhaseMap::new([
+                0.0.into(),
+                0.25.into(),
+                0.5.into(),
+                0.75.into(),
+            ])),
+            symmetry: Some(Symmetry { axis: 3 }),
+            duration_ms: NonZeroU32::new(70000).unwrap(),
+            ramp_type: RampType::EasedOklch {
+                easing: Easing::CubicBezier {
+                    x1: 0.25.into(),
+                    y1: 0.0.into(),
+                    x2: 0.75.into(),
+                    y2: 1.0.into(),
+                },
+                hue_arc: HueArc::Longest,
+            },
+            ramp_ratio: 1.0.into(),
+            start_color: Color::new(0x10, 0x20, 0x30),
+            end_color: Color::new(0x40, 0x50, 0x60),
+            gradient: Some(gradient),
+            twinkle: Some(Twinkle {
+                seed: 0x12345678,
+                color: Color::white(),
+                density: 0.5.into(),
+                decay_ms: NonZeroU16::new(1200).unwrap(),
+            }),
+            playback: Playback::Repeat(NonZeroU16::new(300).unwrap()),
+        }
+    }
+
+    #[test]
+    fn checksum() {
+        // Check value from the CRC catalogue
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+        assert_eq!(crc16(&[]), 0xFFFF);
+    }
+
+    #[test]
+    fn golden_vectors() {
+        // Changing these breaks Cues stored by older versions, increase
+        // FORMAT_VERSION instead
+        let cue: Cue = Cue::default();
+        let (buffer, len) = encode(&cue);
+        #[rustfmt::skip]
+        let expected = [
+            1, 18, 0, // version, payload length
+            12, 0, 0xFF, 0x0F, 12, // channels, flags, channel mask, time divisor
+            0xE8, 0x03, 0, 0, // duration
+            0, 0x80, // ramp type, ramp ratio
+            0, 0, 0, 0, 0, 0, // colors
+            0, // playback
+            0xCF, 0x03, // CRC
+        ];
+        assert_eq!(&buffer[..len], &expected);
+
+        let (buffer, len) = encode(&full_cue());
+        #[rustfmt::skip]
+        let expected = [
+            1, 49, 0, // version, payload length
+            4, 0x1F, 0x0D, 2, // channels, flags, channel mask, time divisor
+            0, 0x40, 0x80, 0xC0, // phase map
+            3, // symmetry
+            0x70, 0x11, 0x01, 0, // duration
+            8, 7, 0x40, 0, 0xC0, 0xFF, 1, // ramp type, easing, hue arc
+            0xFF, // ramp ratio
+            0x10, 0x20, 0x30, 0x40, 0x50, 0x60, // colors
+            2, 0, 1, 2, 3, 0xFF, 4, 5, 6, // gradient
+            0x78, 0x56, 0x34, 0x12, 0xFF, 0xFF, 0xFF, 0x80, 0xB0, 0x04, // twinkle
+            3, 0x2C, 0x01, // playback
+            0x86, 0xDE, // CRC
+        ];
+        assert_eq!(&buffer[..len], &expected);
+    }
+
+    /// Two layers, the upper one blended onto the lower one
+    fn schedule() -> Schedule<4> {
+        let mut schedule = Schedule::new();
+        schedule.add(1, Cue::default()).unwrap();
+        schedule
+            .add_layer(Layer {
+                blend_mode: BlendMode::Screen,
+                opacity: 0.5.into(),
+                ..Layer::new(5, full_cue())
+            })
+            .unwrap();
+        schedule
+    }
+
+    #[test]
+    fn schedule_golden_vectors() {
+        // Changing these breaks Schedules stored by older versions, increase
+        // FORMAT_VERSION instead
+        let mut buffer = [0; 128];
+        let len = Schedule::<4>::new().encode_into(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], &[1, 1, 0, 0, 0x44, 0xC5]);
+
+        let len = schedule().encode_into(&mut buffer).unwrap();
+        #[rustfmt::skip]
+        let expected = [
+            1, 73, 0, // version, payload length
+            2, // number of layers
+            5, 4, 0x80, // priority, blend mode, opacity
+            4, 0x1F, 0x0D, 2, // channels, flags, channel mask, time divisor
+            0, 0x40, 0x80, 0xC0, // phase map
+            3, // symmetry
+            0x70, 0x11, 0x01, 0, // duration
+            8, 7, 0x40, 0, 0xC0, 0xFF, 1, // ramp type, easing, hue arc
+            0xFF, // ramp ratio
+            0x10, 0x20, 0x30, 0x40, 0x50, 0x60, // colors
+            2, 0, 1, 2, 3, 0xFF, 4, 5, 6, // gradient
+            0x78, 0x56, 0x34, 0x12, 0xFF, 0xFF, 0xFF, 0x80, 0xB0, 0x04, // twinkle
+            3, 0x2C, 0x01, // playback
+            1, 0, 0xFF, // priority, blend mode, opacity
+            4, 0, 0x0F, 4, // channels, flags, channel mask, time divisor
+            0xE8, 0x03, 0, 0, // duration
+            0, 0x80, // ramp type, ramp ratio
+            0, 0, 0, 0, 0, 0, // colors
+            0, // playback
+            0x59, 0x7B, // CRC
+        ];
+        assert_eq!(&buffer[..len], &expected);
+    }
+
+    #[test]
+    fn schedule_round_trip() {
+        let schedule = schedule();
+        let mut buffer = [0; 128];
+        let len = schedule.encode_into(&mut buffer).unwrap();
+        assert_eq!(len, schedule.encoded_len());
+        assert_eq!(Schedule::decode(&buffer[..len]), Ok(schedule.clone()));
+        assert_eq!(
+            schedule.encode_into(&mut buffer[..len - 1]),
+            Err(BufferTooSmall { needed: len })
+        );
+
+        let mut full: Schedule = Schedule::new();
+        for priority in 0..MAX_LAYERS as u8 {
+            full.add(priority, Cue::starry_sky()).unwrap();
+        }
+        let mut buffer = [0; 512];
+        let len = full.encode_into(&mut buffer).unwrap();
+        assert_eq!(Schedule::decode(&buffer[..len]), Ok(full));
+    }
+
+    #[test]
+    fn invalid_schedule() {
+        let mut buffer = [0; 128];
+        let len = schedule().encode_into(&mut buffer).unwrap();
+        for end in 0..len {
+            assert_eq!(
+                Schedule::<4>::decode(&buffer[..end]),
+                Err(DecodeError::UnexpectedEnd)
+            );
+        }
+        assert_eq!(
+            Schedule::<12>::decode(&buffer[..len]),
+            Err(DecodeError::ChannelCount(4))
+        );
+
+        let decode_modified = |index: usize, byte: u8| {
+            let mut buffer = buffer;
+            buffer[index] = byte;
+            let crc = crc16(&buffer[..len - CRC_LEN]);
+            buffer[len - CRC_LEN..len].copy_from_slice(&crc.to_le_bytes());
+            Schedule::<4>::decode(&buffer[..len])
+        };
+        assert_eq!(decode_modified(3, 9), Err(DecodeError::InvalidValue));
+        assert_eq!(decode_modified(5, 6), Err(DecodeError::InvalidValue));
+        // The symmetry axis of the first Cue
+        assert_eq!(
+            decode_modified(15, 8),
+            Err(DecodeError::InvalidCue(CueError::InvalidSymmetryAxis(8)))
+        );
+        // Layers are put in order, like when deserializing
+        let reordered = decode_modified(56, 9).unwrap();
+        assert_eq!(reordered.layers().next().unwrap().cue, Cue::default());
+    }
+
+    #[test]
+    fn round_trip() {
+        let presets: [Cue; 5] = [
+            Cue::rainbow(),
+            Cue::black_white_jump(),
+            Cue::white_breathing(),
+            Cue::sunset(),
+            Cue::starry_sky(),
+        ];
+        for cue in presets.iter() {
+            let (buffer, len) = encode(cue);
+            assert_eq!(len, cue.encoded_len());
+            assert_eq!(Cue::decode(&buffer[..len]), Ok(cue.clone()));
+            // Trailing data is ignored
+            assert_eq!(Cue::decode(&buffer), Ok(cue.clone()));
+        }
+        let cue = full_cue();
+        let (buffer, len) = encode(&cue);
+        assert_eq!(Cue::decode(&buffer[..len]), Ok(cue));
+
+        let mut buffer = [0; 23];
+        assert_eq!(Cue::<12>::default().encode_into(&mut buffer), Ok(23));
+        assert_eq!(
+            Cue::<12>::default().encode_into(&mut buffer[..22]),
+            Err(BufferTooSmall { needed: 23 })
+        );
+    }
+
+    #[test]
+    fn max_len() {
+        let mut gradient = Gradient::new();
+        for stop in 0..MAX_STOPS {
+            gradient.add(Fraction::default(), Color::white()).unwrap();
+            assert_eq!(gradient.len(), stop + 1);
+        }
+        let full = full_cue();
+        let cue = Cue::<255> {
+            phase_map: Some(PhaseMap::new([Fraction::default(); 255])),
+            symmetry: full.symmetry,
+            ramp_type: full.ramp_type,
+            gradient: Some(gradient),
+            twinkle: full.twinkle,
+            playback: full.playback,
+            ..Default::default()
+        };
+        assert_eq!(cue.encoded_len(), MAX_ENCODED_LEN);
+    }
+
+    #[test]
+    fn invalid_data() {
+        let (mut buffer, len) = encode(&full_cue());
+        for end in 0..len {
+            assert_eq!(
+                Cue::<4>::decode(&buffer[..end]),
+                Err(DecodeError::UnexpectedEnd)
+            );
+        }
+        assert_eq!(
+            Cue::<5>::decode(&buffer[..len]),
+            Err(DecodeError::ChannelCount(4))
+        );
+        buffer[20] ^= 0x10;
+        assert_eq!(
+            Cue::<4>::decode(&buffer[..len]),
+            Err(DecodeError::ChecksumMismatch)
+        );
+        buffer[0] = 2;
+        assert_eq!(
+            Cue::<4>::decode(&buffer[..len]),
+            Err(DecodeError::UnsupportedVersion(2))
+        );
+
+        // Values with a correct checksum are still checked
+        let cue: Cue = Cue::default();
+        let (valid, len) = encode(&cue);
+        let decode_modified = |index: usize, bytes: &[u8]| {
+            let mut buffer = valid;
+            buffer[index..index + bytes.len()].copy_from_slice(bytes);
+            let crc = crc16(&buffer[..len - CRC_LEN]);
+            buffer[len - CRC_LEN..len].copy_from_slice(&crc.to_le_bytes());
+            Cue::<12>::decode(&buffer[..len])
+        };
+        assert_eq!(decode_modified(4, &[0x20]), Err(DecodeError::InvalidValue));
+        assert_eq!(decode_modified(6, &[0x1F]), Err(DecodeError::InvalidValue));
+        assert_eq!(
+            decode_modified(7, &[0]),
+            Err(DecodeError::InvalidCue(CueError::ZeroTimeDivisor))
+        );
+        assert_eq!(
+            decode_modified(8, &[0, 0]),
+            Err(DecodeError::InvalidCue(CueError::ZeroDuration))
+        );
+        assert_eq!(decode_modified(12, &[9]), Err(DecodeError::InvalidValue));
+        assert_eq!(decode_modified(20, &[4]), Err(DecodeError::InvalidValue));
+        // The length in the header has to match the fields
+        for payload_len in [17, 19].iter() {
+            let mut buffer = valid;
+            buffer[1] = *payload_len;
+            let end = HEADER_LEN + *payload_len as usize;
+            let crc = crc16(&buffer[..end]);
+            buffer[end..end + CRC_LEN].copy_from_slice(&crc.to_le_bytes());
+            let expected = if *payload_len < 18 {
+                DecodeError::UnexpectedEnd
+            } else {
+                DecodeError::InvalidValue
+            };
+            assert_eq!(Cue::<12>::decode(&buffer), Err(expected));
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use crate::color::Color;
+use crate::cue::{Fraction, RampType};
+use core::fmt;
+use fixed::types::U0F8;
+use serde::de::{Error, SeqAccess, Visitor};
+use serde::ser::SerializeSeq;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Maximum number of color stops in a [`Gradient`].
+/// The stops are stored inline, so this directly affects the memory footprint.
+pub const MAX_STOPS: usize = 8;
+
+/// A color at a certain position inside a [`Gradient`]
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct ColorStop {
+    /// Where in the gradient the color is reached, between 0 and 1
+    pub position: Fraction,
+    pub color: Color,
+}
+
+/// A bounded list of color stops, ordered by their position.
+/// Between two stops, colors are mixed according to the [`RampType`] of the Cue.
+/// Easing is applied to the position in the whole gradient, not between each pair of stops.
+/// Before the first and after the last stop, the color of that stop is used.
+/// Will serialize into a sequence of [`ColorStop`]s
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Gradient {
+    stops: [ColorStop; MAX_STOPS],
+    len: u8,
+}
+
+impl Default for Gradient {
+    fn default() -> Gradient {
+        Gradient {
+            stops: [ColorStop {
+                position: U0F8::MIN.into(),
+                color: Color::black(),
+            }; MAX_STOPS],
+            len: 0,
+        }
+    }
+}
+
+impl Gradient {
+    pub fn new() -> Gradient {
+        Gradient::default()
+    }
+
+    /// Add a color stop, keeping all stops ordered by position. Stops with the
+    /// same position are ordered in the sequence they were added in.
+    /// If the gradient is already full, the stop is handed back.
+    pub fn add(&mut self, position: Fraction, color: Color) -> Result<(), ColorStop> {
+        let stop = ColorStop { position, color };
+        let len = self.len();
+        if len == MAX_STOPS {
+            return Err(stop);
+        }
+
+        let index = self
+            .stops()
+            .iter()
+            .position(|other| other.position > position)
+            .unwrap_or(len);
+        self.stops[index..=len].rotate_right(1);
+        self.stops[index] = stop;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// All color stops, ordered by position
+    pub fn stops(&self) -> &[ColorStop] {
+        &self.stops[..self.len()]
+    }
+
+    /// Number of color stops
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Calculate the color at a given position in the gradient.
+    /// An empty gradient is black.
+    /// # Examples
+    /// ```
+    /// use iris_lib::color::Color;
+    /// use iris_lib::cue::RampType;
+    /// use iris_lib::gradient::Gradient;
+    /// use fixed::types::U0F8;
+    /// use fixed_macro::types::U0F8;
+    ///
+    /// let mut gradient = Gradient::new();
+    /// gradient.add(0.0.into(), Color::new(255, 0, 0)).unwrap();
+    /// gradient.add(0.5.into(), Color::new(0, 255, 0)).unwrap();
+    /// gradient.add(1.0.into(), Color::new(0, 0, 255)).unwrap();
+    ///
+    /// let color_at = |position| gradient.color_at(position, RampType::LinearRGB);
+    /// assert_eq!(color_at(U0F8!(0)), Color::new(255, 0, 0));
+    /// assert_eq!(color_at(U0F8!(0.25)), Color::new(127, 128, 0));
+    /// assert_eq!(color_at(U0F8!(0.5)), Color::new(0, 255, 0));
+    /// assert_eq!(color_at(U0F8::MAX), Color::new(0, 0, 255));
+    /// ```
+    pub fn color_at(&self, position: U0F8, ramp_type: RampType) -> Color {
+        let stops = self.stops();
+        // Index of the first stop that lies behind the position
+        let next = stops
+            .iter()
+            .position(|stop| stop.position.0 > position)
+            .unwrap_or(stops.len());
+
+        if next == 0 {
+            return stops.first().map_or(Color::black(), |stop| stop.color);
+        }
+        let start = stops[next - 1];
+        let end = match stops.get(next) {
+            Some(end) => end,
+            None => return start.color,
+        };
+
+        // Map position to a factor between the two surrounding stops. Actual formula:
+        // (position - start) / (end - start)
+        let factor =
+            (position - start.position.0).saturating_div(end.position.0 - start.position.0);
+
+        ramp_type.mix(start.color, end.color, factor)
+    }
+}
+
+impl Serialize for Gradient {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.len()))?;
+        for stop in self.stops() {
+            seq.serialize_element(stop)?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Gradient {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Gradient, D::Error> {
+        struct GradientVisitor;
+
+        impl<'de> Visitor<'de> for GradientVisitor {
+            type Value = Gradient;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(formatter, "a sequence of at most {} color stops", MAX_STOPS)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Gradient, A::Error> {
+                let mut gradient = Gradient::new();
+                while let Some(stop) = seq.next_element::<ColorStop>()? {
+                    gradient
+                        .add(stop.position, stop.color)
+                        .map_err(|_| A::Error::invalid_length(MAX_STOPS + 1, &self))?;
+                }
+                Ok(gradient)
+            }
+        }
+
+        deserializer.deserialize_seq(GradientVisitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::gradient::*;
+    use fixed_macro::types::U0F8;
+
+    fn red() -> Color {
+        Color::new(255, 0, 0)
+    }
+
+    fn blue() -> Color {
+        Color::new(0, 0, 255)
+    }
+
+    #[test]
+    fn stops_are_ordered() {
+        let mut gradient = Gradient::new();
+        gradient.add(0.75.into(), blue()).unwrap();
+        gradient.add(0.25.into(), red()).unwrap();
+        gradient.add(0.5.into(), Color::white()).unwrap();
+
+        let positions: [f32; 3] = [0.25, 0.5, 0.75];
+        assert!(gradient
+            .stops()
+            .iter()
+            .zip(positions.iter())
+            .all(|(stop, position)| f32::from(stop.position) == *position));
+
+        for _ in gradient.len()..MAX_STOPS {
+            gradient.add(0.0.into(), red()).unwrap();
+        }
+        assert!(gradient.add(0.0.into(), red()).is_err());
+    }
+
+    #[test]
+    fn outside_of_stops() {
+        let mut gradient = Gradient::new();
+        assert_eq!(
+            gradient.color_at(U0F8!(0.5), RampType::LinearRGB),
+            Color::black()
+        );
+
+        gradient.add(0.25.into(), red()).unwrap();
+        gradient.add(0.75.into(), blue()).unwrap();
+        assert_eq!(gradient.color_at(U0F8!(0), RampType::LinearRGB), red());
+        assert_eq!(gradient.color_at(U0F8!(0.25), RampType::LinearRGB), red());
+        assert_eq!(gradient.color_at(U0F8!(0.75), RampType::LinearRGB), blue());
+        assert_eq!(gradient.color_at(U0F8::MAX, RampType::LinearRGB), blue());
+    }
+
+    #[test]
+    fn jump_between_stops() {
+        let mut gradient = Gradient::new();
+        gradient.add(0.0.into(), red()).unwrap();
+        gradient.add(0.5.into(), Color::white()).unwrap();
+        gradient.add(0.5.into(), blue()).unwrap();
+
+        assert_eq!(gradient.color_at(U0F8!(0.49), RampType::Jump), red());
+        assert_eq!(gradient.color_at(U0F8!(0.5), RampType::Jump), blue());
+        assert_eq!(gradient.color_at(U0F8::MAX, RampType::Jump), blue());
+    }
+}
@@ -1,7 +1,31 @@
 //! This crate defines the data structure and operations on the Freilite Iris 16,
 //! a smart vandal switch with a lit ring consisting of 12 individual RGB LEDs.
+//!
+//! All calculations use integer and fixed-point math, so they are cheap on
+//! microcontrollers without an FPU and produce the same results on every target.
+//! The `float` feature (enabled by default) adds conversions from and to the
+//! floating point color types of the `palette` crate.
 
 #![no_std]
+pub mod builder;
+pub mod calibration;
 pub mod color;
 pub mod cue;
-
+pub mod dither;
+pub mod easing;
+pub mod encoding;
+pub mod gradient;
+pub mod oklab;
+pub mod output;
+pub mod phase;
+pub mod power;
+pub mod prng;
+pub mod protocol;
+pub mod render;
+pub mod schedule;
+mod serde_array;
+pub mod settings;
+pub mod simulator;
+pub mod sysex;
+pub mod twinkle;
+pub mod wide;
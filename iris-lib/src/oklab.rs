@@ -0,0 +1,367 @@
+use crate::color::Color;
+use serde::{Deserialize, Serialize};
+
+/// A color in the OKLab color space, which is designed so that equal distances
+/// look equally different. Mixing colors in OKLab keeps the perceived
+/// brightness even, without the dark or washed out colors of RGB mixing.
+/// All components have 16 fractional bits, so 65536 is 1.
+/// See <https://bottosson.github.io/posts/oklab/>
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Oklab {
+    /// Perceived lightness, from 0 for black to 65536 for white
+    pub l: i32,
+    /// Green (negative) to red (positive)
+    pub a: i32,
+    /// Blue (negative) to yellow (positive)
+    pub b: i32,
+}
+
+/// OKLab in polar coordinates: lightness, chroma and hue, like HSL, but with
+/// perceptually even steps. All components except the hue have 16 fractional bits.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Oklch {
+    /// Perceived lightness, from 0 for black to 65536 for white
+    pub l: i32,
+    /// Distance from grey. The most colorful sRGB colors are about 0.32 (21000)
+    pub chroma: i32,
+    /// Position on the color wheel, where a full turn of 360° is 65536.
+    /// Unlike HSL, 0 is pink instead of red.
+    pub hue: u16,
+}
+
+/// sRGB values converted to linear light, with 24 fractional bits
+const SRGB_TO_LINEAR: [u32; 256] = [
+    0, 5092, 10185, 15277, 20369, 25462, 30554, 35646, 40739, 45831, 50923, 56146, 61682, 67524,
+    73676, 80144, 86931, 94043, 101483, 109255, 117364, 125813, 134607, 143749, 153244, 163095,
+    173306, 183880, 194821, 206133, 217819, 229883, 242327, 255157, 268373, 281981, 295983, 310382,
+    325182, 340386, 355996, 372016, 388449, 405298, 422565, 440255, 458369, 476910, 495881, 515286,
+    535127, 555406, 576126, 597291, 618902, 640963, 663476, 686443, 709868, 733752, 758099, 782910,
+    808189, 833938, 860159, 886854, 914027, 941680, 969814, 998433, 1027538, 1057133, 1087218,
+    1117798, 1148873, 1180447, 1212520, 1245097, 1278179, 1311767, 1345865, 1380475, 1415598,
+    1451237, 1487394, 1524071, 1561270, 1598994, 1637244, 1676023, 1715332, 1755173, 1795550,
+    1836463, 1877915, 1919907, 1962442, 2005522, 2049149, 2093324, 2138049, 2183328, 2229161,
+    2275550, 2322497, 2370005, 2418074, 2466708, 2515908, 2565675, 2616012, 2666920, 2718402,
+    2770458, 2823092, 2876304, 2930097, 2984472, 3039432, 3094977, 3151110, 3207832, 3265145,
+    3323052, 3381553, 3440650, 3500346, 3560641, 3621538, 3683038, 3745144, 3807855, 3871176,
+    3935106, 3999648, 4064803, 4130573, 4196960, 4263965, 4331589, 4399836, 4468706, 4538200,
+    4608321, 4679069, 4750448, 4822457, 4895099, 4968376, 5042288, 5116838, 5192027, 5267856,
+    5344328, 5421443, 5499204, 5577611, 5656667, 5736372, 5816729, 5897738, 5979402, 6061722,
+    6144699, 6228335, 6312631, 6397589, 6483210, 6569496, 6656448, 6744068, 6832357, 6921317,
+    7010948, 7101253, 7192233, 7283889, 7376223, 7469237, 7562930, 7657306, 7752366, 7848110,
+    7944540, 8041658, 8139465, 8237963, 8337152, 8437035, 8537612, 8638885, 8740855, 8843524,
+    8946893, 9050964, 9155737, 9261215, 9367397, 9474287, 9581885, 9690192, 9799210, 9908940,
+    10019383, 10130542, 10242416, 10355008, 10468318, 10582349, 10697100, 10812575, 10928773,
+    11045697, 11163346, 11281724, 11400831, 11520668, 11641236, 11762538, 11884573, 12007344,
+    12130852, 12255098, 12380082, 12505807, 12632274, 12759484, 12887438, 13016137, 13145583,
+    13275776, 13406719, 13538412, 13670857, 13804054, 13938006, 14072712, 14208175, 14344396,
+    14481375, 14619114, 14757615, 14896878, 15036905, 15177696, 15319253, 15461578, 15604671,
+    15748533, 15893166, 16038571, 16184750, 16331702, 16479430, 16627934, 16777216,
+];
+
+/// Fractional bits of all fixed-point numbers in the internal calculations
+const FRACTION_BITS: u32 = 24;
+const ONE: i64 = 1 << FRACTION_BITS;
+
+/// Linear sRGB to cone responses (LMS).
+/// Rows add up to exactly 1, so white stays white.
+const RGB_TO_LMS: [[i64; 3]; 3] = [
+    [6915928, 8998167, 863121],
+    [3555151, 11420243, 1801822],
+    [1481469, 4726458, 10569289],
+];
+
+/// Cube root of LMS to OKLab.
+/// The rows for a and b add up to exactly 0, so greys have no chroma.
+const LMS_TO_LAB: [[i64; 3]; 3] = [
+    [3530836, 13314697, -68317],
+    [33185308, -40745016, 7559708],
+    [434598, 13132731, -13567329],
+];
+
+/// OKLab to cube root of LMS
+const LAB_TO_LMS: [[i64; 3]; 3] = [
+    [16777216, 6649445, 3620586],
+    [16777216, -1771025, -1071295],
+    [16777216, -1501295, -21667532],
+];
+
+/// LMS to linear sRGB
+const LMS_TO_RGB: [[i64; 3]; 3] = [
+    [68396376, -55494192, 3875032],
+    [-21280858, 43784463, -5726389],
+    [-70399, -11801406, 28649021],
+];
+
+/// Multiply a 3x3 matrix with a vector
+fn transform(matrix: &[[i64; 3]; 3], vector: [i64; 3]) -> [i64; 3] {
+    let row = |row: &[i64; 3]| {
+        let sum = row[0] * vector[0] + row[1] * vector[1] + row[2] * vector[2];
+        shift_round(sum, FRACTION_BITS)
+    };
+    [row(&matrix[0]), row(&matrix[1]), row(&matrix[2])]
+}
+
+/// Divide by 2^shift and round to nearest, also for negative numbers
+fn shift_round(value: i64, shift: u32) -> i64 {
+    (value + (1 << (shift - 1))) >> shift
+}
+
+/// Cube root, also for negative numbers
+fn cbrt(x: i64) -> i64 {
+    // Shift by another 2 * FRACTION_BITS, so the cube root has FRACTION_BITS again
+    let mut remainder = (x.unsigned_abs() as u128) << (2 * FRACTION_BITS);
+    // Bitwise integer cube root, see Hacker's Delight, 2nd edition, 11-2
+    let mut root: u128 = 0;
+    for shift in (0..=126).rev().step_by(3) {
+        root <<= 1;
+        let b = 3 * root * (root + 1) + 1;
+        if remainder >> shift >= b {
+            remainder -= b << shift;
+            root += 1;
+        }
+    }
+    root as i64 * x.signum()
+}
+
+fn cube(x: i64) -> i64 {
+    shift_round(shift_round(x * x, FRACTION_BITS) * x, FRACTION_BITS)
+}
+
+/// Convert linear light back to sRGB by looking up the nearest value
+fn encode_srgb(linear: i64) -> u8 {
+    let linear = linear.clamp(0, ONE) as u32;
+    let above = SRGB_TO_LINEAR.partition_point(|&value| value < linear);
+    match above {
+        0 => 0,
+        _ if linear - SRGB_TO_LINEAR[above - 1] < SRGB_TO_LINEAR[above] - linear => {
+            (above - 1) as u8
+        }
+        _ => above as u8,
+    }
+}
+
+/// OKLab with the full internal precision
+pub(crate) fn to_lab(color: Color) -> [i64; 3] {
+    let rgb: [u8; 3] = color.into();
+    let linear = rgb.map(|value| SRGB_TO_LINEAR[value as usize] as i64);
+    let lms = transform(&RGB_TO_LMS, linear).map(cbrt);
+    transform(&LMS_TO_LAB, lms)
+}
+
+/// Inverse of [`to_lab`]. Colors outside of the sRGB gamut are clipped
+pub(crate) fn from_lab(lab: [i64; 3]) -> Color {
+    let lms = transform(&LAB_TO_LMS, lab).map(cube);
+    let [red, green, blue] = transform(&LMS_TO_RGB, lms).map(encode_srgb);
+    Color::new(red, green, blue)
+}
+
+/// Bits that are dropped when converting to the public representation
+const EXTRA_BITS: u32 = FRACTION_BITS - 16;
+
+impl From<Color> for Oklab {
+    fn from(color: Color) -> Oklab {
+        let [l, a, b] = to_lab(color).map(|value| shift_round(value, EXTRA_BITS) as i32);
+        Oklab { l, a, b }
+    }
+}
+
+impl From<Oklab> for Color {
+    /// Colors outside of the sRGB gamut are clipped
+    fn from(lab: Oklab) -> Color {
+        from_lab([lab.l, lab.a, lab.b].map(|value| (value as i64) << EXTRA_BITS))
+    }
+}
+
+/// Colors with less chroma are treated as grey, as their hue is meaningless
+pub(crate) const GREY_CHROMA: i64 = 1 << 8;
+
+/// atan(2^-i) for CORDIC, where a full turn is 2^32
+const CORDIC_ANGLES: [i64; 24] = [
+    536870912, 316933406, 167458907, 85004756, 42667331, 21354465, 10679838, 5340245, 2670163,
+    1335087, 667544, 333772, 166886, 83443, 41722, 20861, 10430, 5215, 2608, 1304, 652, 326, 163,
+    81,
+];
+
+/// Every CORDIC step increases the length of the vector. This is the inverse
+/// of the total gain after all steps
+const CORDIC_SCALE: i64 = 10188014;
+
+/// Half a turn for CORDIC angles
+const HALF_TURN: i64 = 1 << 31;
+
+/// Chroma and hue of the a and b components of OKLab
+pub(crate) fn to_polar([a, b]: [i64; 2]) -> (i64, u16) {
+    // Rotate the vector onto the positive x axis, keeping track of the angle
+    let (mut x, mut y) = (a, b);
+    let mut angle: i64 = 0;
+    // CORDIC only converges for angles up to about 90°, so start in the right half
+    if x < 0 {
+        x = -x;
+        y = -y;
+        angle = HALF_TURN;
+    }
+    for (step, step_angle) in CORDIC_ANGLES.iter().enumerate() {
+        let (previous_x, previous_y) = (x, y);
+        if y > 0 {
+            x += previous_y >> step;
+            y -= previous_x >> step;
+            angle += step_angle;
+        } else {
+            x -= previous_y >> step;
+            y += previous_x >> step;
+            angle -= step_angle;
+        }
+    }
+    // Truncating the angle wraps around the color wheel
+    let hue = (shift_round(angle, 16) as i16) as u16;
+    (shift_round(x * CORDIC_SCALE, FRACTION_BITS), hue)
+}
+
+/// Inverse of [`to_polar`]
+pub(crate) fn from_polar(chroma: i64, hue: u16) -> [i64; 2] {
+    // Rotate a vector on the x axis by the hue
+    let mut x = shift_round(chroma * CORDIC_SCALE, FRACTION_BITS);
+    let mut y = 0;
+    let mut angle = (hue as i16 as i64) << 16;
+    // CORDIC only converges for angles up to about 90°, so start in the right half
+    if angle.abs() > HALF_TURN / 2 {
+        x = -x;
+        angle -= HALF_TURN * angle.signum();
+    }
+    for (step, step_angle) in CORDIC_ANGLES.iter().enumerate() {
+        let (previous_x, previous_y) = (x, y);
+        if angle > 0 {
+            x -= previous_y >> step;
+            y += previous_x >> step;
+            angle -= step_angle;
+        } else {
+            x += previous_y >> step;
+            y -= previous_x >> step;
+            angle += step_angle;
+        }
+    }
+    [x, y]
+}
+
+impl From<Oklab> for Oklch {
+    fn from(lab: Oklab) -> Oklch {
+        // Use the extra bits for precision
+        let (chroma, hue) = to_polar([lab.a as i64, lab.b as i64].map(|value| value << EXTRA_BITS));
+        Oklch {
+            l: lab.l,
+            chroma: shift_round(chroma, EXTRA_BITS) as i32,
+            hue,
+        }
+    }
+}
+
+impl From<Oklch> for Oklab {
+    fn from(lch: Oklch) -> Oklab {
+        let [a, b] = from_polar((lch.chroma as i64) << EXTRA_BITS, lch.hue)
+            .map(|value| shift_round(value, EXTRA_BITS) as i32);
+        Oklab { l: lch.l, a, b }
+    }
+}
+
+impl From<Color> for Oklch {
+    fn from(color: Color) -> Oklch {
+        Oklab::from(color).into()
+    }
+}
+
+impl From<Oklch> for Color {
+    fn from(lch: Oklch) -> Color {
+        Oklab::from(lch).into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::oklab::*;
+    extern crate std;
+
+    /// Reference implementation with floating point math
+    fn float_oklab(color: Color) -> [f64; 3] {
+        let rgb: [u8; 3] = color.into();
+        let [r, g, b] = rgb.map(|value| {
+            let value = value as f64 / 255.0;
+            if value <= 0.04045 {
+                value / 12.92
+            } else {
+                ((value + 0.055) / 1.055).powf(2.4)
+            }
+        });
+        let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+        let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+        let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+        [
+            0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+            1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+            0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+        ]
+    }
+
+    fn sample_colors() -> impl Iterator<Item = Color> {
+        static VALUES: [u8; 10] = [0, 1, 2, 17, 64, 100, 128, 200, 254, 255];
+        VALUES.iter().flat_map(|r| {
+            VALUES
+                .iter()
+                .flat_map(move |g| VALUES.iter().map(move |b| Color::new(*r, *g, *b)))
+        })
+    }
+
+    #[test]
+    fn matches_float() {
+        for color in sample_colors() {
+            let lab = Oklab::from(color);
+            let expected = float_oklab(color);
+            for (actual, expected) in [lab.l, lab.a, lab.b].iter().zip(expected.iter()) {
+                let actual = *actual as f64 / 65536.0;
+                assert!(
+                    (actual - expected).abs() < 0.0002,
+                    "{:?}: {:?}, expected {:?}",
+                    color,
+                    lab,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn lossless_round_trip() {
+        for color in sample_colors() {
+            assert_eq!(Color::from(Oklab::from(color)), color);
+            assert_eq!(Color::from(Oklch::from(color)), color);
+        }
+        for value in 0..=u8::MAX {
+            let grey = Color::new(value, value, value);
+            assert_eq!(Color::from(Oklab::from(grey)), grey);
+            let lab = Oklab::from(grey);
+            assert!(lab.a.abs() <= 1 && lab.b.abs() <= 1, "{:?}", lab);
+        }
+        let white = Oklab::from(Color::white());
+        assert_eq!(white.l, 65536);
+    }
+
+    #[test]
+    fn polar_coordinates() {
+        // Reference values from the float implementation, hue in degrees
+        let colors: [(Color, f64, f64); 4] = [
+            (Color::new(255, 0, 0), 0.2577, 29.23),
+            (Color::new(0, 255, 0), 0.2948, 142.50),
+            (Color::new(0, 0, 255), 0.3132, 264.05),
+            (Color::new(255, 255, 0), 0.2110, 109.77),
+        ];
+        for (color, chroma, hue) in colors.iter() {
+            let lch = Oklch::from(*color);
+            let actual_hue = lch.hue as f64 * 360.0 / 65536.0;
+            assert!(
+                (lch.chroma as f64 / 65536.0 - chroma).abs() < 0.0005,
+                "{:?}",
+                lch
+            );
+            assert!((actual_hue - hue).abs() < 0.05, "{:?}", lch);
+        }
+    }
+}
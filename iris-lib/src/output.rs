@@ -0,0 +1,443 @@
+use crate::color::Color;
+use crate::cue::Fraction;
+use crate::wide::WideColor;
+use core::convert::TryFrom;
+use core::fmt;
+use fixed::types::{U0F8, U8F8};
+use serde::{Deserialize, Serialize};
+
+/// Exponent of a gamma curve. Usual values for LEDs are between 2 and 3.
+/// An [`OutputStage`] rejects a gamma of 0, which negative values saturate to.
+/// Will serialize into an [`f32`]
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(from = "f32")]
+#[serde(into = "f32")]
+pub struct Gamma(pub(crate) U8F8);
+
+impl Default for Gamma {
+    fn default() -> Gamma {
+        2.2.into()
+    }
+}
+
+impl From<Gamma> for f32 {
+    fn from(value: Gamma) -> f32 {
+        value.0.to_num()
+    }
+}
+
+impl From<f32> for Gamma {
+    fn from(value: f32) -> Gamma {
+        Gamma(U8F8::saturating_from_num(value))
+    }
+}
+
+impl From<U8F8> for Gamma {
+    fn from(value: U8F8) -> Gamma {
+        Gamma(value)
+    }
+}
+
+/// A gamma of 0 would turn every color, even black, into full brightness
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidGamma(pub Gamma);
+
+impl fmt::Display for InvalidGamma {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "gamma {} must be greater than 0", f32::from(self.0))
+    }
+}
+
+/// Settings of an [`OutputStage`], which is serialized as these
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct OutputSettings {
+    /// Master brightness between 0 and 1, applied after the gamma curve
+    pub brightness: Fraction,
+    /// Exponent of the gamma curve, 1 keeps the values linear
+    pub gamma: Gamma,
+}
+
+impl Default for OutputSettings {
+    fn default() -> OutputSettings {
+        OutputSettings {
+            brightness: U0F8::MAX.into(),
+            gamma: Gamma::default(),
+        }
+    }
+}
+
+/// Last step before colors are sent to the LEDs. Corrects the rendered colors
+/// with a gamma curve, so brightness is perceived as linear, and applies the
+/// master brightness. Both are combined into a precomputed table, so applying
+/// them to a color is just a lookup. The table has 8 fractional bits, so dark
+/// colors don't all round to black, see [`OutputStage::apply_precise`].
+///
+/// The table takes 512 bytes of RAM. It can't live in flash, as it changes
+/// with the brightness, which the firmware sets at runtime.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(try_from = "OutputSettings")]
+#[serde(into = "OutputSettings")]
+pub struct OutputStage {
+    settings: OutputSettings,
+    table: [U8F8; 256],
+}
+
+impl Default for OutputStage {
+    fn default() -> OutputStage {
+        OutputStage::with_valid_settings(OutputSettings::default())
+    }
+}
+
+impl TryFrom<OutputSettings> for OutputStage {
+    type Error = InvalidGamma;
+
+    fn try_from(settings: OutputSettings) -> Result<OutputStage, InvalidGamma> {
+        check_gamma(settings.gamma)?;
+        Ok(OutputStage::with_valid_settings(settings))
+    }
+}
+
+impl From<OutputStage> for OutputSettings {
+    fn from(output: OutputStage) -> OutputSettings {
+        output.settings
+    }
+}
+
+impl OutputStage {
+    pub fn new(brightness: Fraction, gamma: Gamma) -> Result<OutputStage, InvalidGamma> {
+        OutputStage::try_from(OutputSettings { brightness, gamma })
+    }
+
+    fn with_valid_settings(settings: OutputSettings) -> OutputStage {
+        let mut output = OutputStage {
+            settings,
+            table: [U8F8::ZERO; 256],
+        };
+        output.update_table();
+        output
+    }
+
+    pub fn settings(&self) -> OutputSettings {
+        self.settings
+    }
+
+    pub fn brightness(&self) -> Fraction {
+        self.settings.brightness
+    }
+
+    pub fn set_brightness(&mut self, brightness: Fraction) {
+        self.settings.brightness = brightness;
+        self.update_table();
+    }
+
+    pub fn gamma(&self) -> Gamma {
+        self.settings.gamma
+    }
+
+    /// Fails without changing anything if the gamma is 0
+    /// # Examples
+    /// ```
+    /// use iris_lib::output::{InvalidGamma, OutputStage};
+    ///
+    /// let mut output = OutputStage::default();
+    /// assert_eq!(output.set_gamma(2.8.into()), Ok(()));
+    /// assert_eq!(output.set_gamma((-1.0).into()), Err(InvalidGamma(0.0.into())));
+    /// assert_eq!(output.gamma(), 2.8.into());
+    /// ```
+    pub fn set_gamma(&mut self, gamma: Gamma) -> Result<(), InvalidGamma> {
+        check_gamma(gamma)?;
+        self.settings.gamma = gamma;
+        self.update_table();
+        Ok(())
+    }
+
+    /// Correct a single color
+    /// # Examples
+    /// ```
+    /// use iris_lib::color::Color;
+    /// use iris_lib::output::OutputStage;
+    ///
+    /// let output = OutputStage::new(0.5.into(), 2.0.into()).unwrap();
+    /// assert_eq!(output.apply(Color::white()), Color::new(128, 128, 128));
+    /// assert_eq!(output.apply(Color::new(128, 64, 0)), Color::new(32, 8, 0));
+    /// ```
+    pub fn apply(&self, color: Color) -> Color {
+        let [red, green, blue] = self.apply_precise(color);
+        let round = |value: U8F8| ((value.to_bits() + (1 << 7)) >> 8) as u8;
+        Color::new(round(red), round(green), round(blue))
+    }
+
+    /// Correct a single color without rounding to 8 bits, e.g. for
+    /// [`TemporalDither`](crate::dither::TemporalDither)
+    /// # Examples
+    /// ```
+    /// use fixed::types::U8F8;
+    /// use iris_lib::color::Color;
+    /// use iris_lib::output::OutputStage;
+    ///
+    /// let output = OutputStage::new(0.5.into(), 2.0.into()).unwrap();
+    /// assert_eq!(output.apply(Color::new(15, 0, 0)), Color::black());
+    /// assert_eq!(
+    ///     output.apply_precise(Color::new(15, 0, 0)),
+    ///     [U8F8::from_num(0.4453125), U8F8::ZERO, U8F8::ZERO]
+    /// );
+    /// ```
+    pub fn apply_precise(&self, color: Color) -> [U8F8; 3] {
+        let [red, green, blue]: [u8; 3] = color.into();
+        [
+            self.table[red as usize],
+            self.table[green as usize],
+            self.table[blue as usize],
+        ]
+    }
+
+    /// Correct a color for LED drivers with 16 bits, interpolating linearly
+    /// between the entries of the table
+    /// # Examples
+    /// ```
+    /// use iris_lib::color::Color;
+    /// use iris_lib::output::OutputStage;
+    /// use iris_lib::wide::WideColor;
+    ///
+    /// let output = OutputStage::new(0.5.into(), 2.0.into()).unwrap();
+    /// assert_eq!(output.apply_wide(WideColor::white()), WideColor::new(32896, 32896, 32896));
+    /// assert_eq!(output.apply_wide(WideColor::new(1000, 0, 0)), WideColor::new(8, 0, 0));
+    /// ```
+    pub fn apply_wide(&self, color: WideColor) -> WideColor {
+        // In units of 1 / (256 * 257), where 255 * 256 * 257 is u16::MAX * 256
+        let correct = |value: u16| ((self.correct_wide(value) + (1 << 7)) >> 8) as u16;
+        let [red, green, blue]: [u16; 3] = color.into();
+        WideColor::new(correct(red), correct(green), correct(blue))
+    }
+
+    /// Like [`OutputStage::apply_wide`], but with 8 integer and 8 fractional
+    /// bits like [`OutputStage::apply_precise`], e.g. for dithering 16 bit
+    /// colors to 8 bit LEDs
+    /// # Examples
+    /// ```
+    /// use fixed::types::U8F8;
+    /// use iris_lib::output::OutputStage;
+    /// use iris_lib::wide::WideColor;
+    ///
+    /// let output = OutputStage::new(0.5.into(), 2.0.into()).unwrap();
+    /// assert_eq!(output.apply_wide_precise(WideColor::white())[0], U8F8::from_num(128));
+    /// assert_eq!(
+    ///     output.apply_wide_precise(WideColor::new(1000, 0, 0)),
+    ///     [U8F8::from_bits(8), U8F8::ZERO, U8F8::ZERO]
+    /// );
+    /// ```
+    pub fn apply_wide_precise(&self, color: WideColor) -> [U8F8; 3] {
+        // Entries are U8F8, so this only removes the factor 257
+        let correct = |value: u16| U8F8::from_bits(((self.correct_wide(value) + 128) / 257) as u16);
+        let [red, green, blue]: [u16; 3] = color.into();
+        [correct(red), correct(green), correct(blue)]
+    }
+
+    /// Interpolate linearly between the entries of the table.
+    /// Returns the bits of a U8F8, multiplied by 257
+    fn correct_wide(&self, value: u16) -> u32 {
+        // u16::MAX = 257 * u8::MAX, so every entry covers 257 values
+        let index = (value / 257) as usize;
+        let remainder = (value % 257) as u32;
+        let lower = self.table[index].to_bits() as u32;
+        let upper = self.table[(index + 1).min(u8::MAX as usize)].to_bits() as u32;
+        lower * 257 + upper * remainder - lower * remainder
+    }
+
+    /// Correct all colors of a rendered frame in place
+    pub fn apply_frame(&self, frame: &mut [Color]) {
+        for color in frame.iter_mut() {
+            *color = self.apply(*color);
+        }
+    }
+
+    fn update_table(&mut self) {
+        let brightness = self.settings.brightness.0.to_bits() as u32;
+        for (value, entry) in self.table.iter_mut().enumerate() {
+            let corrected = pow(value as u8, self.settings.gamma.0) as u64;
+            // corrected ≤ 1 << 16, so the brightness can be applied with rounding in 64 bits
+            *entry = U8F8::from_bits(((corrected * brightness as u64 + (1 << 7)) >> 8) as u16);
+        }
+    }
+}
+
+fn check_gamma(gamma: Gamma) -> Result<(), InvalidGamma> {
+    if gamma.0 == U8F8::ZERO {
+        Err(InvalidGamma(gamma))
+    } else {
+        Ok(())
+    }
+}
+
+/// 2^(-2^-(i + 1)) for i in 0..16, with 32 fractional bits
+const EXP2_NEG: [u64; 16] = [
+    0xB504_F334,
+    0xD744_FCCB,
+    0xEAC0_C6E8,
+    0xF525_7D15,
+    0xFA83_B2DB,
+    0xFD3E_0C0D,
+    0xFE9E_115C,
+    0xFF4E_CB59,
+    0xFFA7_5652,
+    0xFFD3_A752,
+    0xFFE9_D2B3,
+    0xFFF4_E91C,
+    0xFFFA_747F,
+    0xFFFD_3A3B,
+    0xFFFE_9D1D,
+    0xFFFF_4E8E,
+];
+
+/// (x / 255)^gamma with 16 fractional bits, so the result is between 0 and 1 << 16.
+/// Actual formula: 2^(gamma * log2(x / 255))
+fn pow(x: u8, gamma: U8F8) -> u32 {
+    match x {
+        0 => 0,
+        u8::MAX => 1 << 16,
+        _ => {
+            // log2(x / 255) is between -8 and 0
+            let log = log2(x as u32) - log2(u8::MAX as u32);
+            let scaled = (log as i64 * gamma.to_bits() as i64) >> 8;
+            exp2_neg((-scaled) as u64)
+        }
+    }
+}
+
+/// log2(x) with 16 fractional bits for x > 0
+fn log2(x: u32) -> i32 {
+    let integer = 31 - x.leading_zeros();
+    // Normalize x to 1 ≤ x < 2 with 30 fractional bits
+    let mut normalized = ((x as u64) << 30) >> integer;
+    let mut result = (integer as i32) << 16;
+    // Squaring doubles the logarithm, so every time it reaches 2, the next bit is set
+    for bit in (0..16).rev() {
+        normalized = (normalized * normalized) >> 30;
+        if normalized >= 2 << 30 {
+            normalized >>= 1;
+            result += 1 << bit;
+        }
+    }
+    result
+}
+
+/// 2^-x with 16 fractional bits for the result and x ≥ 0
+fn exp2_neg(x: u64) -> u32 {
+    let integer = x >> 16;
+    if integer >= 32 {
+        return 0;
+    }
+    // Multiply the factors for each set bit of the fractional part
+    let mut result: u64 = 1 << 32;
+    for (bit, factor) in EXP2_NEG.iter().enumerate() {
+        if x & (1 << (15 - bit)) != 0 {
+            result = (result * factor) >> 32;
+        }
+    }
+    // Reduce from 32 to 16 fractional bits with rounding
+    let shift = 16 + integer;
+    ((result + (1 << (shift - 1))) >> shift) as u32
+}
+
+#[cfg(test)]
+mod test {
+    use crate::output::*;
+    extern crate std;
+
+    #[test]
+    fn linear_is_identity() {
+        let output = OutputStage::new(U0F8::MAX.into(), 1.0.into()).unwrap();
+        for value in 0..=u8::MAX {
+            assert_eq!(
+                output.apply(Color::new(value, value, value)),
+                Color::new(value, value, value)
+            );
+        }
+    }
+
+    #[test]
+    fn matches_float_curves() {
+        let gammas: [f32; 5] = [0.5, 1.8, 2.2, 2.8, 4.0];
+        let brightnesses: [u8; 4] = [255, 200, 128, 20];
+        for gamma in gammas.iter() {
+            for brightness in brightnesses.iter() {
+                let output =
+                    OutputStage::new(U0F8::from_bits(*brightness).into(), (*gamma).into()).unwrap();
+                let gamma = f32::from(output.gamma()) as f64;
+                for value in 0..=u8::MAX {
+                    let expected = (value as f64 / 255.0).powf(gamma) * *brightness as f64;
+                    let [actual, _, _]: [u8; 3] = output.apply(Color::new(value, 0, 0)).into();
+                    assert!(
+                        (expected - actual as f64).abs() <= 1.0,
+                        "{}^{} * {} = {}, expected {}",
+                        value,
+                        gamma,
+                        brightness,
+                        actual,
+                        expected
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wide_matches_narrow() {
+        let output = OutputStage::default();
+        for value in 0..=u8::MAX {
+            let wide = output.apply_wide(Color::new(value, 0, 0).into());
+            assert_eq!(Color::from(wide), output.apply(Color::new(value, 0, 0)));
+            assert_eq!(
+                output.apply_wide_precise(Color::new(value, 0, 0).into()),
+                output.apply_precise(Color::new(value, 0, 0))
+            );
+        }
+        let mut previous = 0;
+        for value in 0..=u16::MAX {
+            let [corrected, _, _]: [u16; 3] = output.apply_wide(WideColor::new(value, 0, 0)).into();
+            assert!(corrected >= previous);
+            previous = corrected;
+        }
+        assert_eq!(previous, u16::MAX);
+    }
+
+    #[test]
+    fn settings() {
+        let mut output = OutputStage::default();
+        assert_eq!(output.apply(Color::white()), Color::white());
+        assert_eq!(output.apply(Color::new(1, 1, 1)), Color::black());
+
+        output.set_brightness(0.0.into());
+        assert_eq!(output.apply(Color::white()), Color::black());
+        output.set_brightness(U0F8::MAX.into());
+        output.set_gamma(1.0.into()).unwrap();
+        assert_eq!(output.apply(Color::new(1, 2, 3)), Color::new(1, 2, 3));
+        assert_eq!(OutputStage::try_from(output.settings()), Ok(output.clone()));
+
+        let mut frame = [Color::white(), Color::new(128, 0, 0)];
+        OutputStage::new(U0F8::MAX.into(), 2.0.into())
+            .unwrap()
+            .apply_frame(&mut frame);
+        assert_eq!(frame, [Color::white(), Color::new(64, 0, 0)]);
+    }
+
+    #[test]
+    fn invalid_gamma() {
+        // Negative values and values below the resolution of Gamma saturate to 0
+        for gamma in [0.0, -2.2, 0.001].iter() {
+            let gamma = Gamma::from(*gamma);
+            assert_eq!(
+                OutputStage::new(U0F8::MAX.into(), gamma),
+                Err(InvalidGamma(Gamma(U8F8::ZERO)))
+            );
+            let mut output = OutputStage::default();
+            assert_eq!(output.set_gamma(gamma), Err(InvalidGamma(gamma)));
+            assert_eq!(output, OutputStage::default());
+        }
+        let settings = OutputSettings {
+            gamma: 0.0.into(),
+            ..OutputSettings::default()
+        };
+        assert!(OutputStage::try_from(settings).is_err());
+    }
+}
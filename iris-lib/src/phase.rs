@@ -0,0 +1,181 @@
+use crate::cue::{Fraction, CHANNELS};
+use crate::prng::Prng;
+use fixed::types::U0F8;
+use serde::{Deserialize, Serialize};
+
+/// Offset of a full duration, which is the same as no offset
+pub(crate) const FULL_TURN: usize = 256;
+
+/// How far ahead each LED is in the animation of a Cue, as a fraction of its
+/// duration, where the offset *k / 256* is the same as channel *k* of a
+/// time divisor of *256*. Patterns move from LEDs with a higher offset towards LEDs with a
+/// lower one. Replaces the evenly spaced offsets of
+/// [`Cue::time_divisor`](crate::cue::Cue::time_divisor) when set on a Cue.
+/// Will serialize into a sequence of offsets
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct PhaseMap<const N: usize = { CHANNELS as usize }> {
+    /// Offset for each LED, starting with channel 0
+    #[serde(with = "crate::serde_array")]
+    pub offsets: [Fraction; N],
+}
+
+impl<const N: usize> PhaseMap<N> {
+    pub fn new(offsets: [Fraction; N]) -> PhaseMap<N> {
+        PhaseMap { offsets }
+    }
+
+    /// Offsets that are `step` apart, rotating towards higher channels like
+    /// [`Cue::time_divisor`](crate::cue::Cue::time_divisor) does. Steps that don't
+    /// divide 1 evenly lead to a visible seam between the last and the first LED.
+    /// # Examples
+    /// ```
+    /// use iris_lib::phase::PhaseMap;
+    /// use fixed::types::U0F8;
+    ///
+    /// let map: PhaseMap<4> = PhaseMap::spaced(U0F8::from_bits(100).into());
+    /// // Wraps around at 256, which is a full duration
+    /// let offsets = [44, 200, 100, 0].map(|bits| U0F8::from_bits(bits).into());
+    /// assert_eq!(map.offsets, offsets);
+    /// ```
+    pub fn spaced(step: Fraction) -> PhaseMap<N> {
+        PhaseMap::from_fn(|channel| {
+            // Lower channels are further ahead, so the pattern moves towards higher channels
+            let steps = (N - 1 - channel) as u32;
+            U0F8::from_bits((steps * step.0.to_bits() as u32 % FULL_TURN as u32) as u8)
+        })
+    }
+
+    /// Patterns start at both ends and meet in the center, as if the first
+    /// half of the LEDs was mirrored onto the second half
+    pub fn mirror() -> PhaseMap<N> {
+        let half = N.div_ceil(2);
+        PhaseMap::from_fn(|channel| ratio(half - 1 - distance_from_end::<N>(channel), half))
+    }
+
+    /// Patterns start in the center and move towards both ends
+    pub fn center_out() -> PhaseMap<N> {
+        let half = N.div_ceil(2);
+        PhaseMap::from_fn(|channel| ratio(distance_from_end::<N>(channel), half))
+    }
+
+    /// Random offsets, so every LED is animated independently.
+    /// The same seed always produces the same offsets.
+    pub fn random_scatter(seed: u32) -> PhaseMap<N> {
+        let prng = Prng::new(seed);
+        PhaseMap::from_fn(|channel| {
+            U0F8::from_bits(prng.derive(channel as u32).next_below(FULL_TURN as u32) as u8)
+        })
+    }
+
+    fn from_fn(offset: impl Fn(usize) -> U0F8) -> PhaseMap<N> {
+        let mut offsets = [Fraction::default(); N];
+        for (channel, fraction) in offsets.iter_mut().enumerate() {
+            *fraction = offset(channel).into();
+        }
+        PhaseMap { offsets }
+    }
+}
+
+/// Splits the ring into two halves along an axis, which mirror each other.
+/// Patterns start on the axis and move along both halves in opposite
+/// directions, like two comets that meet on the other side of the ring.
+/// Use [`Cue::reverse`](crate::cue::Cue::reverse) to let them move towards the axis instead.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Symmetry {
+    /// Position of the axis in half LEDs: *0* goes through channel 0,
+    /// *1* goes between channel 0 and 1, *2* through channel 1 and so on.
+    /// The axis always goes through the center of the ring, so it has to be
+    /// below twice the number of LEDs, see [`Cue::validate`](crate::cue::Cue::validate).
+    pub axis: u8,
+}
+
+impl Symmetry {
+    /// Map a channel to the channel on the first half that mirrors it.
+    /// Channels on the axis map to 0, the ones furthest away to `channels / 2`.
+    /// Axes that aren't on the ring don't wrap around. They still map every
+    /// channel to one on the first half, so Cues that weren't validated can be rendered.
+    /// # Examples
+    /// ```
+    /// use iris_lib::phase::Symmetry;
+    ///
+    /// // Axis through channel 3 and 9
+    /// let symmetry = Symmetry { axis: 6 };
+    /// assert_eq!(symmetry.fold(3, 12), 0);
+    /// assert_eq!(symmetry.fold(2, 12), symmetry.fold(4, 12));
+    /// assert_eq!(symmetry.fold(9, 12), 6);
+    /// ```
+    pub fn fold(&self, channel: u8, channels: usize) -> u8 {
+        // Calculate in half LEDs, so axes between two LEDs are possible
+        let turn = 2 * channels;
+        let distance = (2 * channel as usize).abs_diff(self.axis as usize);
+        (distance.min(turn.saturating_sub(distance)) / 2) as u8
+    }
+}
+
+/// Number of LEDs between the channel and the closer end
+fn distance_from_end<const N: usize>(channel: usize) -> usize {
+    channel.min(N - 1 - channel)
+}
+
+/// numerator / denominator in 256ths, so channel *k* of *N* is *k / N* of the
+/// duration ahead like with a time divisor of *N*. Requires numerator < denominator.
+fn ratio(numerator: usize, denominator: usize) -> U0F8 {
+    U0F8::from_bits(((numerator * FULL_TURN + denominator / 2) / denominator) as u8)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::phase::*;
+
+    fn bits<const N: usize>(map: PhaseMap<N>) -> [u8; N] {
+        let mut bits = [0; N];
+        for (bits, offset) in bits.iter_mut().zip(map.offsets.iter()) {
+            *bits = offset.0.to_bits();
+        }
+        bits
+    }
+
+    #[test]
+    fn symmetric_presets() {
+        assert_eq!(
+            bits(PhaseMap::<8>::mirror()),
+            [192, 128, 64, 0, 0, 64, 128, 192]
+        );
+        assert_eq!(
+            bits(PhaseMap::<8>::center_out()),
+            [0, 64, 128, 192, 192, 128, 64, 0]
+        );
+        // The center LED of an odd number of LEDs has its own offset
+        assert_eq!(bits(PhaseMap::<5>::center_out()), [0, 85, 171, 85, 0]);
+        assert_eq!(bits(PhaseMap::<1>::mirror()), [0]);
+    }
+
+    #[test]
+    fn random_scatter() {
+        let map: PhaseMap = PhaseMap::random_scatter(7);
+        assert_eq!(map, PhaseMap::random_scatter(7));
+        assert_ne!(map, PhaseMap::random_scatter(8));
+        // Not all the same
+        assert!(map.offsets.iter().any(|offset| *offset != map.offsets[0]));
+    }
+
+    #[test]
+    fn symmetry() {
+        let fold = |axis, channels| {
+            let symmetry = Symmetry { axis };
+            (0..channels as u8).map(move |channel| symmetry.fold(channel, channels))
+        };
+        assert!(fold(0, 8).eq([0, 1, 2, 3, 4, 3, 2, 1].iter().copied()));
+        assert!(fold(1, 8).eq([0, 0, 1, 2, 3, 3, 2, 1].iter().copied()));
+        assert!(fold(4, 8).eq([2, 1, 0, 1, 2, 3, 4, 3].iter().copied()));
+        // Odd number of LEDs
+        assert!(fold(0, 5).eq([0, 1, 2, 2, 1].iter().copied()));
+        assert!(fold(15, 8).eq([0, 1, 2, 3, 3, 2, 1, 0].iter().copied()));
+        // Axes that aren't on the ring don't wrap around
+        assert!(fold(30, 8).ne(fold(14, 8)));
+        for axis in 16..=u8::MAX {
+            assert!(fold(axis, 8).all(|channel| channel <= 4));
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use crate::color::Color;
+use serde::{Deserialize, Serialize};
+
+/// Current drawn by a single LED in mA, for each color at full brightness
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct ChannelCurrent {
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+}
+
+impl Default for ChannelCurrent {
+    /// 20 mA per color, which is typical for WS2812 LEDs
+    fn default() -> ChannelCurrent {
+        ChannelCurrent::new(20, 20, 20)
+    }
+}
+
+impl ChannelCurrent {
+    pub fn new(red: u16, green: u16, blue: u16) -> ChannelCurrent {
+        ChannelCurrent { red, green, blue }
+    }
+
+    /// Current of a single color in mA, multiplied by 255
+    fn scaled(&self, color: Color) -> u64 {
+        let [red, green, blue]: [u8; 3] = color.into();
+        red as u64 * self.red as u64
+            + green as u64 * self.green as u64
+            + blue as u64 * self.blue as u64
+    }
+}
+
+/// Estimates the current a frame draws and dims frames that exceed the budget.
+/// The current is assumed to be proportional to the values sent to the LEDs,
+/// so this is applied after the [`OutputStage`](crate::output::OutputStage)
+/// and the [`CalibrationProfile`](crate::calibration::CalibrationProfile).
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct PowerLimiter {
+    /// Current drawn by every LED
+    pub current: ChannelCurrent,
+    /// Maximum current all LEDs together may draw, in mA
+    pub budget_ma: u32,
+}
+
+impl Default for PowerLimiter {
+    /// 500 mA, which is what a USB 2.0 port has to provide
+    fn default() -> PowerLimiter {
+        PowerLimiter::new(ChannelCurrent::default(), 500)
+    }
+}
+
+impl PowerLimiter {
+    pub fn new(current: ChannelCurrent, budget_ma: u32) -> PowerLimiter {
+        PowerLimiter { current, budget_ma }
+    }
+
+    /// Estimated current of a frame in mA, rounded up
+    /// # Examples
+    /// ```
+    /// use iris_lib::color::Color;
+    /// use iris_lib::power::PowerLimiter;
+    ///
+    /// let limiter = PowerLimiter::default();
+    /// assert_eq!(limiter.frame_current_ma(&[Color::white(); 12]), 720);
+    /// assert_eq!(limiter.frame_current_ma(&[Color::new(128, 0, 0); 12]), 121);
+    /// ```
+    pub fn frame_current_ma(&self, frame: &[Color]) -> u32 {
+        let scaled = self.scaled_current(frame);
+        scaled.div_ceil(u8::MAX as u64) as u32
+    }
+
+    /// Scale all colors of the frame down by the same factor if it draws more
+    /// than the budget. Returns whether the frame was dimmed.
+    /// # Examples
+    /// ```
+    /// use iris_lib::color::Color;
+    /// use iris_lib::power::PowerLimiter;
+    ///
+    /// let limiter = PowerLimiter::default();
+    /// let mut frame = [Color::white(); 12];
+    /// assert!(limiter.limit_frame(&mut frame));
+    /// assert_eq!(frame, [Color::new(177, 177, 177); 12]);
+    /// assert!(limiter.frame_current_ma(&frame) <= 500);
+    /// ```
+    pub fn limit_frame(&self, frame: &mut [Color]) -> bool {
+        let scaled = self.scaled_current(frame);
+        let budget = self.budget_ma as u64 * u8::MAX as u64;
+        if scaled <= budget {
+            return false;
+        }
+        // Rounding down keeps the dimmed frame within the budget
+        let dim = |value: u8| (value as u64 * budget / scaled) as u8;
+        for color in frame.iter_mut() {
+            let [red, green, blue]: [u8; 3] = (*color).into();
+            *color = Color::new(dim(red), dim(green), dim(blue));
+        }
+        true
+    }
+
+    /// Current of a frame in mA, multiplied by 255
+    fn scaled_current(&self, frame: &[Color]) -> u64 {
+        frame.iter().map(|color| self.current.scaled(*color)).sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::power::*;
+
+    #[test]
+    fn within_budget() {
+        let limiter = PowerLimiter::new(ChannelCurrent::new(15, 10, 12), 300);
+        let mut frame = [Color::new(255, 0, 0); 20];
+        assert_eq!(limiter.frame_current_ma(&frame), 300);
+        assert!(!limiter.limit_frame(&mut frame));
+        assert_eq!(frame, [Color::new(255, 0, 0); 20]);
+        assert_eq!(limiter.frame_current_ma(&[]), 0);
+    }
+
+    #[test]
+    fn scales_uniformly() {
+        let limiter = PowerLimiter::new(ChannelCurrent::new(16, 12, 8), 100);
+        let mut frame = [
+            Color::white(),
+            Color::new(200, 100, 0),
+            Color::new(0, 0, 50),
+            Color::black(),
+        ];
+        assert_eq!(limiter.frame_current_ma(&frame), 55);
+        // Exceeds the budget
+        let limiter = PowerLimiter::new(limiter.current, 20);
+        assert!(limiter.limit_frame(&mut frame));
+        assert!(limiter.frame_current_ma(&frame) <= 20);
+        assert_eq!(
+            frame,
+            [
+                Color::new(93, 93, 93),
+                Color::new(72, 36, 0),
+                Color::new(0, 0, 18),
+                Color::black(),
+            ]
+        );
+    }
+
+    #[test]
+    fn zero_budget() {
+        let limiter = PowerLimiter::new(ChannelCurrent::default(), 0);
+        let mut frame = [Color::new(1, 2, 3); 12];
+        assert!(limiter.limit_frame(&mut frame));
+        assert_eq!(frame, [Color::black(); 12]);
+    }
+}
@@ -0,0 +1,96 @@
+//! Small pseudo random number generator without any dependencies.
+//! It is not suitable for cryptography, but produces the same numbers on
+//! every target, so random effects look identical in the hub and on the device.
+
+/// Pseudo random number generator based on xorshift32.
+/// Streams for different purposes can be derived from a single seed with
+/// [`Prng::derive`], so effects can look up random numbers for any point in
+/// time without keeping state between frames.
+/// # Examples
+/// ```
+/// use iris_lib::prng::Prng;
+///
+/// let mut a = Prng::new(42).derive(3);
+/// let mut b = Prng::new(42).derive(3);
+/// assert_eq!(a.next_u32(), b.next_u32());
+/// assert!(a.next_below(10) < 10);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Prng {
+    state: u32,
+}
+
+impl Prng {
+    /// Create a generator. Similar seeds produce unrelated sequences.
+    pub fn new(seed: u32) -> Prng {
+        // xorshift gets stuck at 0, so replace it with an arbitrary non-zero state
+        let state = match mix(seed) {
+            0 => 0x9E37_79B9,
+            state => state,
+        };
+        Prng { state }
+    }
+
+    /// Create an independent generator for `key`, e.g. an LED or a time slot.
+    /// Doesn't change the state of `self`.
+    pub fn derive(&self, key: u32) -> Prng {
+        Prng::new(self.state ^ mix(key.wrapping_add(0x9E37_79B9)))
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+
+    /// Random number in the range from 0 to `bound` (exclusive).
+    /// A bound of 0 always returns 0.
+    pub fn next_below(&mut self, bound: u32) -> u32 {
+        // Scale instead of using modulo, which would favor small numbers
+        ((self.next_u32() as u64 * bound as u64) >> 32) as u32
+    }
+}
+
+/// Scramble all bits of `x`, so neighboring inputs produce unrelated outputs.
+/// This is the finalizer of MurmurHash3.
+fn mix(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x85EB_CA6B);
+    x ^= x >> 13;
+    x = x.wrapping_mul(0xC2B2_AE35);
+    x ^= x >> 16;
+    x
+}
+
+#[cfg(test)]
+mod test {
+    use crate::prng::*;
+
+    #[test]
+    fn deterministic() {
+        let mut a = Prng::new(1234);
+        let mut b = Prng::new(1234);
+        for _ in 0..100 {
+            assert_eq!(a.next_u32(), b.next_u32());
+        }
+        // Fixed sequence, so the hub and the device always agree
+        let mut prng = Prng::new(0);
+        assert_eq!(prng.next_u32(), 0x510C_4619);
+        assert_ne!(Prng::new(1).next_u32(), Prng::new(2).next_u32());
+        assert_ne!(prng.derive(1), prng.derive(2));
+    }
+
+    #[test]
+    fn evenly_distributed() {
+        let mut prng = Prng::new(7);
+        let mut buckets = [0u32; 10];
+        for _ in 0..10_000 {
+            buckets[prng.next_below(10) as usize] += 1;
+        }
+        assert!(buckets.iter().all(|count| (900..1100).contains(count)));
+        assert_eq!(prng.next_below(0), 0);
+    }
+}
@@ -0,0 +1,748 @@
+//! Commands Iris Hub sends to the hardware and the responses of the hardware,
+//! transferred as SysEx messages, see [`crate::sysex`]. The data of every
+//! message starts with a code for the kind of command or response, followed
+//! by its fields. Cues are stored in numbered slots on the hardware and are
+//! transferred in the binary encoding of [`crate::encoding`].
+//!
+//! | Code   | Message                    | Fields                                                        |
+//! |--------|----------------------------|---------------------------------------------------------------|
+//! | `0x01` | [`Command::Identify`]      |                                                               |
+//! | `0x02` | [`Command::UploadCue`]     | slot, encoded Cue                                             |
+//! | `0x03` | [`Command::DeleteSlot`]    | slot                                                          |
+//! | `0x04` | [`Command::LaunchSlot`]    | slot                                                          |
+//! | `0x05` | [`Command::ReadSlot`]      | slot                                                          |
+//! | `0x06` | [`Command::SetBrightness`] | brightness                                                    |
+//! | `0x41` | [`Response::Identity`]     | protocol version, firmware version (3 bytes), channels, slots |
+//! | `0x42` | [`Response::Ack`]          |                                                               |
+//! | `0x43` | [`Response::Cue`]          | slot, encoded Cue                                             |
+//! | `0x44` | [`Response::Error`]        | error code, argument                                          |
+
+use crate::cue::{Cue, Fraction, CHANNELS};
+use crate::encoding::{DecodeError, MAX_ENCODED_LEN};
+use crate::sysex::{self, SysExError, END, MANUFACTURER_ID, START};
+use core::convert::TryInto;
+use core::fmt;
+use fixed::types::U0F8;
+
+/// Version of the command set, reported in [`Identity`]
+pub const PROTOCOL_VERSION: u8 = 1;
+
+/// Length of the longest message data: code, slot and an encoded Cue. Every
+/// [`Parser`] holds a buffer of this length, 362 bytes of the 2.5 KB of RAM
+/// of the device, as a Cue can only be decoded once its checksum arrived.
+pub const MAX_DATA_LEN: usize = 2 + MAX_ENCODED_LEN;
+
+/// Length of the longest SysEx message, enough for a buffer that can hold any
+/// [`Command`] or [`Response`]. The data is packed inside of this buffer, so
+/// sending a message takes no other buffer. A buffer of this length takes up
+/// 418 bytes though, so the firmware should only keep one around while it
+/// sends a message.
+pub const MAX_MESSAGE_LEN: usize = 4 + sysex::packed_len(MAX_DATA_LEN);
+
+const IDENTIFY: u8 = 0x01;
+const UPLOAD_CUE: u8 = 0x02;
+const DELETE_SLOT: u8 = 0x03;
+const LAUNCH_SLOT: u8 = 0x04;
+const READ_SLOT: u8 = 0x05;
+const SET_BRIGHTNESS: u8 = 0x06;
+
+const IDENTITY: u8 = 0x41;
+const ACK: u8 = 0x42;
+const SLOT_CUE: u8 = 0x43;
+const ERROR: u8 = 0x44;
+
+/// Sent from Iris Hub to the hardware
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command<const N: usize = { CHANNELS as usize }> {
+    /// Ask for the firmware version and capabilities, answered with [`Response::Identity`]
+    Identify,
+    /// Store a Cue in a slot, replacing the Cue stored there
+    UploadCue { slot: u8, cue: Cue<N> },
+    /// Remove the Cue stored in a slot
+    DeleteSlot { slot: u8 },
+    /// Start playing the Cue stored in a slot
+    LaunchSlot { slot: u8 },
+    /// Ask for the Cue stored in a slot, answered with [`Response::Cue`]
+    ReadSlot { slot: u8 },
+    /// Change the master brightness of the [`OutputStage`](crate::output::OutputStage)
+    SetBrightness(Fraction),
+}
+
+/// Sent from the hardware to Iris Hub, once for every [`Command`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Response<const N: usize = { CHANNELS as usize }> {
+    Identity(Identity),
+    /// The command was executed
+    Ack,
+    /// The Cue stored in a slot
+    Cue {
+        slot: u8,
+        cue: Cue<N>,
+    },
+    /// The command couldn't be executed
+    Error(DeviceError),
+}
+
+/// Firmware version and capabilities of a device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Identity {
+    /// [`PROTOCOL_VERSION`] the firmware was built with
+    pub protocol_version: u8,
+    /// Major, minor and patch version
+    pub firmware_version: [u8; 3],
+    /// Number of LEDs, Cues have to be uploaded with the same number
+    pub channels: u8,
+    /// Number of slots Cues can be stored in
+    pub slots: u8,
+}
+
+/// Reasons why a device couldn't execute a [`Command`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceError {
+    /// The device doesn't know the command with this code
+    UnknownCommand(u8),
+    /// The fields of the command couldn't be read
+    MalformedCommand,
+    /// The uploaded Cue is invalid, e.g. it has the wrong number of channels
+    InvalidCue,
+    /// The device doesn't have this slot
+    InvalidSlot(u8),
+    /// No Cue is stored in this slot
+    EmptySlot(u8),
+}
+
+impl DeviceError {
+    fn code(&self) -> [u8; 2] {
+        match *self {
+            DeviceError::UnknownCommand(code) => [1, code],
+            DeviceError::MalformedCommand => [2, 0],
+            DeviceError::InvalidCue => [3, 0],
+            DeviceError::InvalidSlot(slot) => [4, slot],
+            DeviceError::EmptySlot(slot) => [5, slot],
+        }
+    }
+
+    fn from_code(code: [u8; 2]) -> Result<DeviceError, ProtocolError> {
+        match code {
+            [1, code] => Ok(DeviceError::UnknownCommand(code)),
+            [2, _] => Ok(DeviceError::MalformedCommand),
+            [3, _] => Ok(DeviceError::InvalidCue),
+            [4, slot] => Ok(DeviceError::InvalidSlot(slot)),
+            [5, slot] => Ok(DeviceError::EmptySlot(slot)),
+            _ => Err(ProtocolError::InvalidValue),
+        }
+    }
+}
+
+impl From<ProtocolError> for DeviceError {
+    /// The error a device responds with to a command it couldn't read
+    fn from(error: ProtocolError) -> DeviceError {
+        match error {
+            ProtocolError::UnknownCode(code) => DeviceError::UnknownCommand(code),
+            ProtocolError::Cue(_) => DeviceError::InvalidCue,
+            _ => DeviceError::MalformedCommand,
+        }
+    }
+}
+
+impl fmt::Display for DeviceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeviceError::UnknownCommand(code) => write!(f, "unknown command {:#04X}", code),
+            DeviceError::MalformedCommand => write!(f, "malformed command"),
+            DeviceError::InvalidCue => write!(f, "invalid Cue"),
+            DeviceError::InvalidSlot(slot) => write!(f, "slot {} doesn't exist", slot),
+            DeviceError::EmptySlot(slot) => write!(f, "slot {} is empty", slot),
+        }
+    }
+}
+
+/// Reasons why a message couldn't be read
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProtocolError {
+    /// A status byte other than a realtime message ended the SysEx message early
+    Interrupted,
+    /// The message is longer than [`MAX_DATA_LEN`]
+    TooLong,
+    /// The SysEx message is invalid
+    SysEx(SysExError),
+    /// The message ends before all fields were read
+    UnexpectedEnd,
+    /// The code of the command or response is unknown
+    UnknownCode(u8),
+    /// The message continues after the last field
+    TrailingData,
+    /// A field has a value that doesn't exist, e.g. an unknown [`DeviceError`]
+    InvalidValue,
+    /// The Cue in the message is invalid
+    Cue(DecodeError),
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProtocolError::Interrupted => write!(f, "message was interrupted"),
+            ProtocolError::TooLong => write!(f, "message is too long"),
+            ProtocolError::SysEx(error) => write!(f, "invalid SysEx message: {}", error),
+            ProtocolError::UnexpectedEnd => write!(f, "message ends unexpectedly"),
+            ProtocolError::UnknownCode(code) => write!(f, "unknown message code {:#04X}", code),
+            ProtocolError::TrailingData => write!(f, "message is longer than its fields"),
+            ProtocolError::InvalidValue => write!(f, "field has an invalid value"),
+            ProtocolError::Cue(error) => write!(f, "invalid Cue: {}", error),
+        }
+    }
+}
+
+impl From<SysExError> for ProtocolError {
+    fn from(error: SysExError) -> ProtocolError {
+        ProtocolError::SysEx(error)
+    }
+}
+
+impl From<DecodeError> for ProtocolError {
+    fn from(error: DecodeError) -> ProtocolError {
+        ProtocolError::Cue(error)
+    }
+}
+
+impl<const N: usize> Command<N> {
+    /// Write the command as a SysEx message to the start of the buffer and
+    /// return its length
+    /// # Examples
+    /// ```
+    /// use iris_lib::protocol::Command;
+    ///
+    /// let mut message = [0; 16];
+    /// let len = Command::<12>::LaunchSlot { slot: 3 }
+    ///     .encode_sysex(0x00, &mut message)
+    ///     .unwrap();
+    /// assert_eq!(message[..len], [0xF0, 0x7D, 0x00, 0x00, 0x04, 0x03, 0xF7]);
+    /// ```
+    pub fn encode_sysex(&self, device_id: u8, buffer: &mut [u8]) -> Result<usize, SysExError> {
+        sysex::write_message_with(device_id, buffer, |data| match self {
+            Command::Identify => write_fields(data, &[IDENTIFY]),
+            Command::UploadCue { slot, cue } => write_cue(data, UPLOAD_CUE, *slot, cue),
+            Command::DeleteSlot { slot } => write_fields(data, &[DELETE_SLOT, *slot]),
+            Command::LaunchSlot { slot } => write_fields(data, &[LAUNCH_SLOT, *slot]),
+            Command::ReadSlot { slot } => write_fields(data, &[READ_SLOT, *slot]),
+            Command::SetBrightness(brightness) => {
+                write_fields(data, &[SET_BRIGHTNESS, brightness.0.to_bits()])
+            }
+        })
+    }
+
+    /// Read a command from the unpacked data of a SysEx message
+    pub fn decode(data: &[u8]) -> Result<Command<N>, ProtocolError> {
+        let (code, fields) = data.split_first().ok_or(ProtocolError::UnexpectedEnd)?;
+        Ok(match *code {
+            IDENTIFY => {
+                let [] = read_fields(fields)?;
+                Command::Identify
+            }
+            UPLOAD_CUE => {
+                let (slot, cue) = read_cue(fields)?;
+                Command::UploadCue { slot, cue }
+            }
+            DELETE_SLOT => {
+                let [slot] = read_fields(fields)?;
+                Command::DeleteSlot { slot }
+            }
+            LAUNCH_SLOT => {
+                let [slot] = read_fields(fields)?;
+                Command::LaunchSlot { slot }
+            }
+            READ_SLOT => {
+                let [slot] = read_fields(fields)?;
+                Command::ReadSlot { slot }
+            }
+            SET_BRIGHTNESS => {
+                let [brightness] = read_fields(fields)?;
+                Command::SetBrightness(U0F8::from_bits(brightness).into())
+            }
+            code => return Err(ProtocolError::UnknownCode(code)),
+        })
+    }
+}
+
+impl<const N: usize> Response<N> {
+    /// Write the response as a SysEx message to the start of the buffer and
+    /// return its length. `device_id` should be the ID of the responding device.
+    pub fn encode_sysex(&self, device_id: u8, buffer: &mut [u8]) -> Result<usize, SysExError> {
+        sysex::write_message_with(device_id, buffer, |data| match self {
+            Response::Identity(identity) => {
+                let [major, minor, patch] = identity.firmware_version;
+                write_fields(
+                    data,
+                    &[
+                        IDENTITY,
+                        identity.protocol_version,
+                        major,
+                        minor,
+                        patch,
+                        identity.channels,
+                        identity.slots,
+                    ],
+                )
+            }
+            Response::Ack => write_fields(data, &[ACK]),
+            Response::Cue { slot, cue } => write_cue(data, SLOT_CUE, *slot, cue),
+            Response::Error(error) => {
+                let [error, argument] = error.code();
+                write_fields(data, &[ERROR, error, argument])
+            }
+        })
+    }
+
+    /// Read a response from the unpacked data of a SysEx message
+    pub fn decode(data: &[u8]) -> Result<Response<N>, ProtocolError> {
+        let (code, fields) = data.split_first().ok_or(ProtocolError::UnexpectedEnd)?;
+        Ok(match *code {
+            IDENTITY => {
+                let [protocol_version, major, minor, patch, channels, slots] = read_fields(fields)?;
+                Response::Identity(Identity {
+                    protocol_version,
+                    firmware_version: [major, minor, patch],
+                    channels,
+                    slots,
+                })
+            }
+            ACK => {
+                let [] = read_fields(fields)?;
+                Response::Ack
+            }
+            SLOT_CUE => {
+                let (slot, cue) = read_cue(fields)?;
+                Response::Cue { slot, cue }
+            }
+            ERROR => Response::Error(DeviceError::from_code(read_fields(fields)?)?),
+            code => return Err(ProtocolError::UnknownCode(code)),
+        })
+    }
+}
+
+fn write_fields(data: &mut [u8], fields: &[u8]) -> Result<usize, SysExError> {
+    let needed = fields.len();
+    data.get_mut(..needed)
+        .ok_or(SysExError::BufferTooSmall { needed })?
+        .copy_from_slice(fields);
+    Ok(needed)
+}
+
+fn write_cue<const N: usize>(
+    data: &mut [u8],
+    code: u8,
+    slot: u8,
+    cue: &Cue<N>,
+) -> Result<usize, SysExError> {
+    let len = write_fields(data, &[code, slot])?;
+    let encoded =
+        cue.encode_into(&mut data[len..])
+            .map_err(|error| SysExError::BufferTooSmall {
+                needed: len + error.needed,
+            })?;
+    Ok(len + encoded)
+}
+
+/// Fields of a fixed length, which have to make up the rest of the message
+fn read_fields<const L: usize>(fields: &[u8]) -> Result<[u8; L], ProtocolError> {
+    fields.try_into().map_err(|_| {
+        if fields.len() < L {
+            ProtocolError::UnexpectedEnd
+        } else {
+            ProtocolError::TrailingData
+        }
+    })
+}
+
+/// A slot followed by an encoded Cue, which have to make up the rest of the message
+fn read_cue<const N: usize>(fields: &[u8]) -> Result<(u8, Cue<N>), ProtocolError> {
+    let (slot, encoded) = fields.split_first().ok_or(ProtocolError::UnexpectedEnd)?;
+    let cue = Cue::decode(encoded)?;
+    if cue.encoded_len() != encoded.len() {
+        return Err(ProtocolError::TrailingData);
+    }
+    Ok((*slot, cue))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    /// Outside of a SysEx message
+    Idle,
+    Manufacturer,
+    DeviceId,
+    Data,
+    /// Inside of a SysEx message that is ignored
+    Skipping,
+}
+
+/// Reads messages from a stream of MIDI bytes, which may arrive in pieces of
+/// any size. Realtime messages (`0xF8` to `0xFF`) may appear anywhere, even
+/// inside of SysEx messages, and are ignored. So are all other MIDI messages
+/// and SysEx messages of other manufacturers. The data is unpacked while it
+/// arrives, so no buffer for the whole SysEx message is needed, but the
+/// unpacked data takes [`MAX_DATA_LEN`] bytes.
+/// # Examples
+/// ```
+/// use iris_lib::protocol::{Command, Parser};
+///
+/// let mut message = [0; 16];
+/// let len = Command::<12>::Identify.encode_sysex(0x7F, &mut message).unwrap();
+///
+/// let mut parser = Parser::new();
+/// // Note on, then the message interleaved with a timing clock
+/// for byte in [0x90, 0x3C, 0x40].iter().chain(&message[..2]) {
+///     assert!(parser.push_command::<12>(*byte).is_none());
+/// }
+/// assert!(parser.push_command::<12>(0xF8).is_none());
+/// let mut commands = message[2..len].iter().filter_map(|byte| parser.push_command(*byte));
+/// assert_eq!(commands.next(), Some(Ok((0x7F, Command::<12>::Identify))));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Parser {
+    state: State,
+    device_id: u8,
+    /// First byte of the current group of packed bytes
+    high_bits: u8,
+    /// Position in the current group of packed bytes
+    group_position: u8,
+    len: usize,
+    data: [u8; MAX_DATA_LEN],
+}
+
+impl Default for Parser {
+    fn default() -> Parser {
+        Parser::new()
+    }
+}
+
+impl Parser {
+    pub fn new() -> Parser {
+        Parser {
+            state: State::Idle,
+            device_id: 0,
+            high_bits: 0,
+            group_position: 0,
+            len: 0,
+            data: [0; MAX_DATA_LEN],
+        }
+    }
+
+    /// Read the next byte. Once a message of this manufacturer is complete,
+    /// returns the device ID and the unpacked data, or why it is invalid.
+    pub fn push(&mut self, byte: u8) -> Option<Result<(u8, &[u8]), ProtocolError>> {
+        let in_message = matches!(
+            self.state,
+            State::Manufacturer | State::DeviceId | State::Data
+        );
+        match byte {
+            // Realtime messages
+            0xF8..=0xFF => None,
+            START => {
+                self.state = State::Manufacturer;
+                if in_message {
+                    Some(Err(ProtocolError::Interrupted))
+                } else {
+                    None
+                }
+            }
+            END => match core::mem::replace(&mut self.state, State::Idle) {
+                State::Data => Some(self.finish()),
+                State::Manufacturer | State::DeviceId => {
+                    Some(Err(ProtocolError::SysEx(SysExError::TooShort)))
+                }
+                State::Idle | State::Skipping => None,
+            },
+            // All other status bytes end SysEx messages
+            0x80..=0xF6 => {
+                self.state = State::Idle;
+                if in_message {
+                    Some(Err(ProtocolError::Interrupted))
+                } else {
+                    None
+                }
+            }
+            _ => self.push_data(byte),
+        }
+    }
+
+    /// Like [`Parser::push`], but also reads the [`Command`] from the data
+    pub fn push_command<const N: usize>(
+        &mut self,
+        byte: u8,
+    ) -> Option<Result<(u8, Command<N>), ProtocolError>> {
+        let result = self.push(byte)?;
+        Some(result.and_then(|(device_id, data)| Ok((device_id, Command::decode(data)?))))
+    }
+
+    /// Like [`Parser::push`], but also reads the [`Response`] from the data
+    pub fn push_response<const N: usize>(
+        &mut self,
+        byte: u8,
+    ) -> Option<Result<(u8, Response<N>), ProtocolError>> {
+        let result = self.push(byte)?;
+        Some(result.and_then(|(device_id, data)| Ok((device_id, Response::decode(data)?))))
+    }
+
+    fn push_data(&mut self, byte: u8) -> Option<Result<(u8, &[u8]), ProtocolError>> {
+        match self.state {
+            // Data of other MIDI messages
+            State::Idle | State::Skipping => {}
+            State::Manufacturer if byte == MANUFACTURER_ID => self.state = State::DeviceId,
+            State::Manufacturer => self.state = State::Skipping,
+            State::DeviceId => {
+                self.device_id = byte;
+                self.group_position = 0;
+                self.len = 0;
+                self.state = State::Data;
+            }
+            State::Data => {
+                if self.group_position == 0 {
+                    self.high_bits = byte;
+                } else if self.len == MAX_DATA_LEN {
+                    self.state = State::Skipping;
+                    return Some(Err(ProtocolError::TooLong));
+                } else {
+                    let high_bit = self.high_bits >> (self.group_position - 1) & 1;
+                    self.data[self.len] = byte | high_bit << 7;
+                    self.len += 1;
+                }
+                self.group_position = (self.group_position + 1) % 8;
+            }
+        }
+        None
+    }
+
+    fn finish(&self) -> Result<(u8, &[u8]), ProtocolError> {
+        // The high bits of a group, but no data
+        if self.group_position == 1 {
+            return Err(ProtocolError::SysEx(SysExError::InvalidLength));
+        }
+        Ok((self.device_id, &self.data[..self.len]))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::color::Color;
+    use crate::protocol::*;
+
+    fn commands() -> [Command<16>; 6] {
+        [
+            Command::Identify,
+            Command::UploadCue {
+                slot: 2,
+                cue: Cue::sunset(),
+            },
+            Command::DeleteSlot { slot: 0 },
+            Command::LaunchSlot { slot: 255 },
+            Command::ReadSlot { slot: 7 },
+            Command::SetBrightness(0.5.into()),
+        ]
+    }
+
+    fn responses() -> [Response<16>; 5] {
+        [
+            Response::Identity(Identity {
+                protocol_version: PROTOCOL_VERSION,
+                firmware_version: [1, 2, 3],
+                channels: 16,
+                slots: 8,
+            }),
+            Response::Ack,
+            Response::Cue {
+                slot: 3,
+                cue: Cue::starry_sky(),
+            },
+            Response::Error(DeviceError::EmptySlot(3)),
+            Response::Error(DeviceError::UnknownCommand(0x7F)),
+        ]
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut parser = Parser::new();
+        let mut message = [0; MAX_MESSAGE_LEN];
+        for command in commands().iter() {
+            let len = command.encode_sysex(0x05, &mut message).unwrap();
+            let (last, bytes) = message[..len].split_last().unwrap();
+            for byte in bytes {
+                assert_eq!(parser.push_command::<16>(*byte), None);
+            }
+            assert_eq!(
+                parser.push_command(*last),
+                Some(Ok((0x05, command.clone())))
+            );
+        }
+        for response in responses().iter() {
+            let len = response.encode_sysex(0x05, &mut message).unwrap();
+            let (last, bytes) = message[..len].split_last().unwrap();
+            for byte in bytes {
+                assert_eq!(parser.push_response::<16>(*byte), None);
+            }
+            assert_eq!(
+                parser.push_response(*last),
+                Some(Ok((0x05, response.clone())))
+            );
+        }
+    }
+
+    #[test]
+    fn interleaved_input() {
+        let mut stream = [0; 1024];
+        let mut len = 0;
+        let mut push = |bytes: &[u8]| {
+            stream[len..len + bytes.len()].copy_from_slice(bytes);
+            len += bytes.len();
+        };
+        // Note on with running status
+        push(&[0x90, 0x3C, 0x40, 0x3E, 0x40]);
+        let mut message = [0; MAX_MESSAGE_LEN];
+        for command in commands().iter() {
+            let message_len = command.encode_sysex(0x01, &mut message).unwrap();
+            for (index, byte) in message[..message_len].iter().enumerate() {
+                push(&[*byte]);
+                // Timing clock and active sensing
+                if index % 5 == 2 {
+                    push(&[0xF8, 0xFE]);
+                }
+            }
+            // SysEx of another manufacturer and a program change
+            push(&[0xF0, 0x41, 0x10, 0x42, 0xF7, 0xC0, 0x05]);
+        }
+
+        let mut parser = Parser::new();
+        let mut parsed = stream[..len]
+            .iter()
+            .filter_map(|byte| parser.push_command::<16>(*byte));
+        for command in commands().iter() {
+            assert_eq!(parsed.next(), Some(Ok((0x01, command.clone()))));
+        }
+        assert_eq!(parsed.next(), None);
+    }
+
+    #[test]
+    fn malformed_messages() {
+        let parse = |bytes: &[u8]| {
+            let mut parser = Parser::new();
+            let mut results = bytes
+                .iter()
+                .filter_map(|byte| parser.push_command::<16>(*byte));
+            let result = results.next();
+            assert_eq!(results.next(), None);
+            result
+        };
+        let command = |data: &[u8]| {
+            let mut message = [0; 512];
+            let len = sysex::write_message(0x00, data, &mut message).unwrap();
+            parse(&message[..len])
+        };
+
+        assert_eq!(parse(&[0xF0, 0x7D]), None);
+        assert_eq!(
+            parse(&[0xF0, 0x7D, 0x00, 0x00, 0x01, 0x90, 0x3C, 0x40]),
+            Some(Err(ProtocolError::Interrupted))
+        );
+        assert_eq!(
+            parse(&[0xF0, 0x7D, 0x00, 0x00, 0xF0, 0x41, 0xF7]),
+            Some(Err(ProtocolError::Interrupted))
+        );
+        assert_eq!(
+            parse(&[0xF0, 0x7D, 0xF7]),
+            Some(Err(ProtocolError::SysEx(SysExError::TooShort)))
+        );
+        assert_eq!(
+            parse(&[0xF0, 0x7D, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF7]),
+            Some(Err(ProtocolError::SysEx(SysExError::InvalidLength)))
+        );
+        assert_eq!(command(&[]), Some(Err(ProtocolError::UnexpectedEnd)));
+        assert_eq!(
+            command(&[0x00]),
+            Some(Err(ProtocolError::UnknownCode(0x00)))
+        );
+        // Responses aren't commands
+        assert_eq!(command(&[ACK]), Some(Err(ProtocolError::UnknownCode(ACK))));
+        assert_eq!(
+            command(&[LAUNCH_SLOT]),
+            Some(Err(ProtocolError::UnexpectedEnd))
+        );
+        assert_eq!(
+            command(&[LAUNCH_SLOT, 1, 2]),
+            Some(Err(ProtocolError::TrailingData))
+        );
+        assert_eq!(
+            command(&[UPLOAD_CUE, 1, 2]),
+            Some(Err(ProtocolError::Cue(DecodeError::UnsupportedVersion(2))))
+        );
+
+        let mut data = [0; 600];
+        data[0] = UPLOAD_CUE;
+        assert_eq!(
+            command(&data[..MAX_DATA_LEN + 1]),
+            Some(Err(ProtocolError::TooLong))
+        );
+        let cue: Cue<16> = Cue::sunset();
+        let len = 2 + cue.encode_into(&mut data[2..]).unwrap();
+        assert_eq!(
+            command(&data[..len + 1]),
+            Some(Err(ProtocolError::TrailingData))
+        );
+        // Cue for a different number of channels
+        let cue: Cue<12> = Cue::default();
+        let len = 2 + cue.encode_into(&mut data[2..]).unwrap();
+        assert_eq!(
+            command(&data[..len]),
+            Some(Err(ProtocolError::Cue(DecodeError::ChannelCount(12))))
+        );
+
+        let mut parser = Parser::new();
+        let mut response = |data: &[u8]| {
+            let mut message = [0; 16];
+            let len = sysex::write_message(0x00, data, &mut message).unwrap();
+            message[..len]
+                .iter()
+                .filter_map(|byte| parser.push_response::<16>(*byte))
+                .next()
+        };
+        assert_eq!(
+            response(&[ERROR, 9, 0]),
+            Some(Err(ProtocolError::InvalidValue))
+        );
+        assert_eq!(
+            response(&[IDENTITY, 1, 2, 3]),
+            Some(Err(ProtocolError::UnexpectedEnd))
+        );
+        // The parser recovers after errors
+        assert_eq!(response(&[ACK]), Some(Ok((0x00, Response::Ack))));
+    }
+
+    #[test]
+    fn device_errors() {
+        let errors = [
+            (
+                ProtocolError::UnknownCode(9),
+                DeviceError::UnknownCommand(9),
+            ),
+            (ProtocolError::TrailingData, DeviceError::MalformedCommand),
+            (
+                ProtocolError::Cue(DecodeError::ChecksumMismatch),
+                DeviceError::InvalidCue,
+            ),
+        ];
+        for (error, expected) in errors.iter() {
+            assert_eq!(DeviceError::from(*error), *expected);
+        }
+        let command: Command<16> = Command::UploadCue {
+            slot: 0,
+            cue: Cue {
+                start_color: Color::white(),
+                ..Default::default()
+            },
+        };
+        let mut message = [0; 64];
+        let len = command.encode_sysex(0x7F, &mut message).unwrap();
+        assert_eq!(
+            command.encode_sysex(0x7F, &mut message[..len - 1]),
+            Err(SysExError::BufferTooSmall { needed: len })
+        );
+    }
+}
@@ -0,0 +1,19 @@
+use crate::color::Color;
+
+/// Calculate the colors of all `N` LEDs at once. This is more efficient than
+/// calling `current_color` for each LED, as all calculations that are the same
+/// for every LED are done only once per frame. The results are identical.
+pub trait Render<const N: usize> {
+    /// Write the colors of all LEDs at a given point in time into `frame`,
+    /// starting with channel 0. If `frame` is shorter than `N`, the
+    /// remaining LEDs are skipped; if it is longer, the remaining entries are
+    /// left untouched.
+    fn render_into(&self, time_ms: u32, frame: &mut [Color]);
+
+    /// Calculate the colors of all LEDs at a given point in time
+    fn render_frame(&self, time_ms: u32) -> [Color; N] {
+        let mut frame = [Color::black(); N];
+        self.render_into(time_ms, &mut frame);
+        frame
+    }
+}
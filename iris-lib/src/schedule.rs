@@ -0,0 +1,426 @@
+use crate::color::{BlendMode, Color};
+use crate::cue::{Cue, CueError, Fraction, CHANNELS};
+use crate::render::Render;
+use fixed::types::U0F8;
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of Cues that can be layered in a single [`Schedule`].
+/// The layers are stored inline, so this directly affects the memory footprint.
+pub const MAX_LAYERS: usize = 8;
+
+/// A single Cue inside a [`Schedule`], together with its priority and how it
+/// is combined with the layers below
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Layer<const N: usize = { CHANNELS as usize }> {
+    /// Layers with a higher priority are displayed on top of lower ones
+    pub priority: u8,
+    /// The Cue to display on this layer
+    pub cue: Cue<N>,
+    /// How the Cue is combined with the layers below
+    #[serde(default)]
+    pub blend_mode: BlendMode,
+    /// How strongly the blended result shows, between 0 and 1.
+    /// Has no effect for [`BlendMode::Replace`]
+    #[serde(default = "full_opacity")]
+    pub opacity: Fraction,
+}
+
+fn full_opacity() -> Fraction {
+    U0F8::MAX.into()
+}
+
+impl<const N: usize> Layer<N> {
+    /// Layer that fully covers the layers below
+    pub fn new(priority: u8, cue: Cue<N>) -> Layer<N> {
+        Layer {
+            priority,
+            cue,
+            blend_mode: BlendMode::Replace,
+            opacity: full_opacity(),
+        }
+    }
+
+    /// Whether the layers below can't be seen through this layer on a channel
+    fn covers(&self, channel: u8) -> bool {
+        let is_opaque = match self.blend_mode {
+            BlendMode::Replace => true,
+            BlendMode::Alpha => self.opacity.0 == U0F8::MAX,
+            _ => false,
+        };
+        is_opaque && self.cue.channels[channel as usize]
+    }
+
+    /// Combine the color of this layer with the color below it
+    fn blend(&self, below: Color, color: Color) -> Color {
+        below.blend(color, self.blend_mode, self.opacity.0)
+    }
+}
+
+/// Multiple Cues layered on top of each other.
+/// For each LED, the layers are combined from the lowest to the highest
+/// priority according to their [`BlendMode`], starting from black.
+/// Disabled channels let lower Cues show through.
+///
+/// `N` is the number of RGB-LEDs in the ring, which defaults to [`CHANNELS`].
+/// Deserialized layers are put in order, just like [`Schedule::add_layer`] does.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(from = "ScheduleLayers<N>")]
+pub struct Schedule<const N: usize = { CHANNELS as usize }> {
+    /// Layers ordered by descending priority. All `Some` entries are at the front.
+    layers: [Option<Layer<N>>; MAX_LAYERS],
+}
+
+/// Serialized form of a [`Schedule`], whose layers might be in any order
+#[derive(Deserialize)]
+struct ScheduleLayers<const N: usize> {
+    layers: [Option<Layer<N>>; MAX_LAYERS],
+}
+
+impl<const N: usize> From<ScheduleLayers<N>> for Schedule<N> {
+    fn from(serialized: ScheduleLayers<N>) -> Schedule<N> {
+        let mut schedule = Schedule::new();
+        // Layers with equal priority keep their order. There are never more than MAX_LAYERS
+        for layer in IntoIterator::into_iter(serialized.layers).flatten() {
+            let _ = schedule.add_layer(layer);
+        }
+        schedule
+    }
+}
+
+impl<const N: usize> Default for Schedule<N> {
+    fn default() -> Schedule<N> {
+        Schedule {
+            layers: Default::default(),
+        }
+    }
+}
+
+impl<const N: usize> Schedule<N> {
+    pub fn new() -> Schedule<N> {
+        Schedule::default()
+    }
+
+    /// Add a Cue with the given priority that covers the layers below.
+    /// Cues with equal priority are displayed below the ones that were added before them.
+    /// If the Schedule is already full, the Cue is handed back.
+    /// # Examples
+    /// ```
+    /// use iris_lib::cue::Cue;
+    /// use iris_lib::schedule::Schedule;
+    ///
+    /// let mut schedule: Schedule = Schedule::new();
+    /// assert!(schedule.add(0, Cue::rainbow()).is_ok());
+    /// assert_eq!(schedule.len(), 1);
+    /// ```
+    pub fn add(&mut self, priority: u8, cue: Cue<N>) -> Result<(), Cue<N>> {
+        self.add_layer(Layer::new(priority, cue))
+            .map_err(|layer| layer.cue)
+    }
+
+    /// Add a layer, e.g. to blend a Cue with the ones below.
+    /// Behaves like [`Schedule::add`] otherwise.
+    /// # Examples
+    /// ```
+    /// use iris_lib::color::{BlendMode, Color};
+    /// use iris_lib::cue::Cue;
+    /// use iris_lib::schedule::{Layer, Schedule};
+    ///
+    /// let red = Color::new(255, 0, 0);
+    /// let mut schedule: Schedule = Schedule::new();
+    /// schedule.add(0, Cue { start_color: red, end_color: red, ..Default::default() }).unwrap();
+    /// // Add a white pulse on top of the red instead of replacing it
+    /// let pulse = Layer {
+    ///     blend_mode: BlendMode::Add,
+    ///     opacity: 0.5.into(),
+    ///     ..Layer::new(1, Cue::white_breathing())
+    /// };
+    /// schedule.add_layer(pulse).unwrap();
+    /// assert_eq!(schedule.current_color(1440, 0), Color::new(255, 128, 128));
+    /// ```
+    pub fn add_layer(&mut self, layer: Layer<N>) -> Result<(), Layer<N>> {
+        let len = self.len();
+        if len == MAX_LAYERS {
+            return Err(layer);
+        }
+
+        // Find the first layer with a lower priority and insert before it
+        let index = self
+            .layers()
+            .position(|other| other.priority < layer.priority)
+            .unwrap_or(len);
+        self.layers[index..=len].rotate_right(1);
+        self.layers[index] = Some(layer);
+        Ok(())
+    }
+
+    /// Remove the layer at `index` (counted from the top) and return it
+    pub fn remove(&mut self, index: usize) -> Option<Layer<N>> {
+        let len = self.len();
+        if index >= len {
+            return None;
+        }
+        let removed = self.layers[index].take();
+        self.layers[index..len].rotate_left(1);
+        removed
+    }
+
+    /// Number of Cues in the Schedule
+    pub fn len(&self) -> usize {
+        self.layers().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers[0].is_none()
+    }
+
+    /// Iterate over all layers, starting with the highest priority
+    pub fn layers(&self) -> impl Iterator<Item = &Layer<N>> {
+        self.layers.iter().map_while(Option::as_ref)
+    }
+
+    /// Calculate the Color of a single LED at a given point in time
+    /// # Panics
+    /// Panics if the channel doesn't exist, see [`Schedule::try_current_color`]
+    pub fn current_color(&self, time_ms: u32, channel: u8) -> Color {
+        self.layers[..self.visible_layers(channel)]
+            .iter()
+            .rev()
+            .flatten()
+            .filter(|layer| layer.cue.channels[channel as usize])
+            .fold(Color::black(), |below, layer| {
+                layer.blend(below, layer.cue.current_color(time_ms, channel))
+            })
+    }
+
+    /// Like [`Schedule::current_color`], but returns an error instead of
+    /// panicking if the channel doesn't exist
+    pub fn try_current_color(&self, time_ms: u32, channel: u8) -> Result<Color, CueError> {
+        if channel as usize >= N {
+            return Err(CueError::InvalidChannel(channel));
+        }
+        Ok(self.current_color(time_ms, channel))
+    }
+
+    /// Number of layers from the top that can be seen on a channel.
+    /// Everything below a layer that covers the channel is hidden.
+    fn visible_layers(&self, channel: u8) -> usize {
+        self.layers()
+            .position(|layer| layer.covers(channel))
+            .map_or(self.len(), |index| index + 1)
+    }
+}
+
+impl<const N: usize> Render<N> for Schedule<N> {
+    fn render_into(&self, time_ms: u32, frame: &mut [Color]) {
+        let mut visible_layers = [0; N];
+        for (channel, visible_layers) in visible_layers.iter_mut().enumerate() {
+            *visible_layers = self.visible_layers(channel as u8);
+        }
+        for color in frame.iter_mut().take(N) {
+            *color = Color::black();
+        }
+
+        // Blend from the bottom up, skipping layers that are hidden on all channels
+        let lowest = visible_layers.iter().max().copied().unwrap_or(0);
+        let layers = self.layers[..lowest].iter().enumerate().rev();
+        for (index, layer) in layers.filter_map(|(index, layer)| Some((index, layer.as_ref()?))) {
+            let timing = layer.cue.prepare_frame(time_ms);
+            for ((channel, color), visible_layers) in (0..N as u8)
+                .zip(frame.iter_mut())
+                .zip(visible_layers.iter())
+            {
+                if index < *visible_layers && layer.cue.channels[channel as usize] {
+                    *color = layer.blend(*color, layer.cue.color_in_frame(&timing, channel));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::schedule::*;
+
+    fn accent() -> Cue {
+        let mut cue = Cue {
+            start_color: Color::white(),
+            end_color: Color::white(),
+            ..Default::default()
+        };
+        // Only light up every third LED
+        for (num, channel) in cue.channels.iter_mut().enumerate() {
+            *channel = num % 3 == 0;
+        }
+        cue
+    }
+
+    #[test]
+    fn disabled_channels_show_lower_layer() {
+        let mut schedule = Schedule::new();
+        schedule.add(0, Cue::rainbow()).unwrap();
+        schedule.add(1, accent()).unwrap();
+
+        let rainbow: Cue = Cue::rainbow();
+        for time_ms in (0..3000).step_by(250) {
+            for channel in 0..12 {
+                let expected = if channel % 3 == 0 {
+                    Color::white()
+                } else {
+                    rainbow.current_color(time_ms, channel)
+                };
+                assert_eq!(schedule.current_color(time_ms, channel), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn render_frame_matches_current_color() {
+        let mut schedule = Schedule::new();
+        schedule.add(0, Cue::rainbow()).unwrap();
+        schedule.add(1, accent()).unwrap();
+        let mut sparse = Cue::white_breathing();
+        sparse.channels = [false; CHANNELS as usize];
+        sparse.channels[1] = true;
+        schedule.add(2, sparse).unwrap();
+        schedule
+            .add_layer(Layer {
+                blend_mode: BlendMode::Screen,
+                opacity: 0.7.into(),
+                ..Layer::new(3, Cue::sunset())
+            })
+            .unwrap();
+
+        for time_ms in (0..7000).step_by(13) {
+            let frame = schedule.render_frame(time_ms);
+            for channel in 0..CHANNELS {
+                assert_eq!(
+                    frame[channel as usize],
+                    schedule.current_color(time_ms, channel)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn ordered_by_priority() {
+        let mut schedule = Schedule::new();
+        schedule.add(1, Cue::white_breathing()).unwrap();
+        schedule.add(5, Cue::rainbow()).unwrap();
+        schedule.add(1, Cue::black_white_jump()).unwrap();
+        schedule.add(3, accent()).unwrap();
+
+        let layers: [_; 4] = [
+            (5, Cue::rainbow()),
+            (3, accent()),
+            (1, Cue::white_breathing()),
+            (1, Cue::black_white_jump()),
+        ];
+        assert!(schedule
+            .layers()
+            .zip(layers.iter())
+            .all(|(layer, (priority, cue))| layer.priority == *priority && layer.cue == *cue));
+
+        assert_eq!(schedule.remove(1).unwrap().cue, accent());
+        assert_eq!(schedule.len(), 3);
+        assert_eq!(
+            schedule.layers().nth(1).unwrap().cue,
+            Cue::white_breathing()
+        );
+        assert!(schedule.remove(3).is_none());
+    }
+
+    #[test]
+    fn empty_and_full() {
+        let mut schedule = Schedule::new();
+        assert!(schedule.is_empty());
+        assert_eq!(schedule.current_color(0, 0), Color::black());
+
+        for _ in 0..MAX_LAYERS {
+            schedule.add(0, Cue::rainbow()).unwrap();
+        }
+        assert_eq!(schedule.add(0, accent()), Err(accent()));
+        assert_eq!(schedule.len(), MAX_LAYERS);
+
+        assert_eq!(
+            schedule.try_current_color(0, CHANNELS - 1),
+            Ok(schedule.current_color(0, CHANNELS - 1))
+        );
+        assert_eq!(
+            schedule.try_current_color(0, CHANNELS),
+            Err(CueError::InvalidChannel(CHANNELS))
+        );
+    }
+
+    #[test]
+    fn blended_layers() {
+        let solid = |color| Cue {
+            start_color: color,
+            end_color: color,
+            ..Default::default()
+        };
+        let mut schedule = Schedule::new();
+        schedule.add(0, solid(Color::new(200, 0, 0))).unwrap();
+        schedule
+            .add_layer(Layer {
+                blend_mode: BlendMode::Add,
+                ..Layer::new(1, solid(Color::new(100, 100, 0)))
+            })
+            .unwrap();
+        schedule
+            .add_layer(Layer {
+                blend_mode: BlendMode::Alpha,
+                opacity: 0.0.into(),
+                ..Layer::new(2, accent())
+            })
+            .unwrap();
+        assert_eq!(schedule.current_color(0, 0), Color::new(255, 100, 0));
+
+        // An opaque layer hides everything below
+        schedule
+            .add_layer(Layer {
+                blend_mode: BlendMode::Alpha,
+                ..Layer::new(3, accent())
+            })
+            .unwrap();
+        assert_eq!(schedule.current_color(0, 0), Color::white());
+        assert_eq!(schedule.current_color(0, 1), Color::new(255, 100, 0));
+        assert_eq!(schedule.render_frame(0)[0], Color::white());
+        assert_eq!(schedule.render_frame(0)[1], Color::new(255, 100, 0));
+    }
+
+    #[test]
+    fn deserialized_layers_are_ordered() {
+        let layer = |priority, cue| Some(Layer::new(priority, cue));
+        // Gaps and the wrong order would hide layers from layers()
+        let unordered: Schedule = Schedule {
+            layers: [
+                None,
+                layer(1, Cue::sunset()),
+                layer(7, Cue::rainbow()),
+                None,
+                layer(1, accent()),
+                layer(3, Cue::rainbow()),
+                None,
+                None,
+            ],
+        };
+        let json = serde_json::to_string(&unordered).unwrap();
+        let schedule: Schedule = serde_json::from_str(&json).unwrap();
+        let expected = [
+            (7, Cue::rainbow()),
+            (3, Cue::rainbow()),
+            (1, Cue::sunset()),
+            (1, accent()),
+        ];
+        assert_eq!(schedule.len(), expected.len());
+        assert!(schedule
+            .layers()
+            .zip(expected.iter())
+            .all(|(layer, (priority, cue))| layer.priority == *priority && layer.cue == *cue));
+        assert!(schedule.layers[4..].iter().all(Option::is_none));
+
+        // Schedules that are in order are unchanged
+        let json = serde_json::to_string(&schedule).unwrap();
+        assert_eq!(serde_json::from_str::<Schedule>(&json).unwrap(), schedule);
+    }
+}
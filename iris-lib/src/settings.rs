@@ -0,0 +1,149 @@
+use crate::calibration::CalibrationProfile;
+use crate::color::Color;
+use crate::cue::CHANNELS;
+use crate::dither::TemporalDither;
+use crate::output::OutputStage;
+use crate::power::PowerLimiter;
+use crate::wide::WideColor;
+use serde::{Deserialize, Serialize};
+
+/// Everything that is specific to a single device instead of a Cue.
+/// Meant to be set up once and stored on the device.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Default)]
+pub struct DeviceSettings<const N: usize = { CHANNELS as usize }> {
+    /// Brightness and gamma correction
+    #[serde(default)]
+    pub output: OutputStage,
+    /// Corrections for the individual LEDs
+    #[serde(default)]
+    pub calibration: CalibrationProfile<N>,
+    /// Limit the current of all LEDs together, unlimited if None
+    #[serde(default)]
+    pub power: Option<PowerLimiter>,
+}
+
+impl<const N: usize> DeviceSettings<N> {
+    pub fn new() -> DeviceSettings<N> {
+        DeviceSettings::default()
+    }
+
+    /// Prepare a rendered frame for the LEDs by applying the output stage,
+    /// the calibration and the power limit in place
+    /// # Examples
+    /// ```
+    /// use iris_lib::calibration::RgbGains;
+    /// use iris_lib::color::Color;
+    /// use iris_lib::cue::Cue;
+    /// use iris_lib::render::Render;
+    /// use iris_lib::settings::DeviceSettings;
+    ///
+    /// let mut settings: DeviceSettings = DeviceSettings::new();
+    /// settings.calibration.white_point = RgbGains::new(255, 240, 200);
+    ///
+    /// let cue: Cue = Cue::white_breathing();
+    /// let mut frame = cue.render_frame(1440);
+    /// settings.apply_frame(&mut frame);
+    /// assert_eq!(frame[0], Color::new(255, 240, 200));
+    /// ```
+    pub fn apply_frame(&self, frame: &mut [Color]) {
+        self.output.apply_frame(frame);
+        self.calibration.apply_frame(frame);
+        if let Some(power) = &self.power {
+            power.limit_frame(frame);
+        }
+    }
+
+    /// Like [`DeviceSettings::apply_frame`], but for a frame rendered with
+    /// 16 bits, e.g. with [`Cue::render_wide_into`](crate::cue::Cue::render_wide_into).
+    /// The output stage and the calibration are calculated with 8 fractional
+    /// bits, which are then turned into the 8 bit values of `frame` with
+    /// `dither`. Call [`TemporalDither::next_frame`] after every frame.
+    /// # Examples
+    /// ```
+    /// use iris_lib::color::Color;
+    /// use iris_lib::dither::TemporalDither;
+    /// use iris_lib::settings::DeviceSettings;
+    /// use iris_lib::wide::WideColor;
+    ///
+    /// let settings: DeviceSettings = DeviceSettings::new();
+    /// // Too dark for 8 bits after the gamma curve
+    /// let dark = WideColor::new(2600, 2600, 2600);
+    /// let mut frame = [Color::from(dark); 12];
+    /// settings.apply_frame(&mut frame);
+    /// assert_eq!(frame[0], Color::black());
+    ///
+    /// // But the dithered frames add up to the precise value
+    /// let mut dither = TemporalDither::new(0);
+    /// let mut sum = 0;
+    /// for _ in 0..256 {
+    ///     settings.apply_frame_dithered(&[dark; 12], &mut frame, &dither);
+    ///     sum += u32::from(<[u8; 3]>::from(frame[0])[0]);
+    ///     dither.next_frame();
+    /// }
+    /// assert_eq!(sum, settings.output.apply_wide_precise(dark)[0].to_bits() as u32);
+    /// ```
+    pub fn apply_frame_dithered(
+        &self,
+        wide_frame: &[WideColor],
+        frame: &mut [Color],
+        dither: &TemporalDither,
+    ) {
+        for (index, (wide, color)) in wide_frame.iter().zip(frame.iter_mut()).enumerate() {
+            let mut precise = self.output.apply_wide_precise(*wide);
+            // Like apply_frame, only N LEDs are calibrated
+            if index < N {
+                precise = self.calibration.apply_precise(index as u8, precise);
+            }
+            *color = dither.dither_color(precise, index as u8);
+        }
+        if let Some(power) = &self.power {
+            power.limit_frame(frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::color::Color;
+    use crate::cue::{Cue, RampType};
+    use crate::dither::TemporalDither;
+    use crate::settings::*;
+    use core::num::NonZeroU32;
+    use fixed::types::U0F8;
+
+    #[test]
+    fn dithered_fade_averages_to_wide_value() {
+        // A slow fade that stays below the first 8 bit step after the gamma curve
+        let fade: Cue = Cue {
+            ramp_type: RampType::LinearRGB,
+            ramp_ratio: U0F8::MAX.into(),
+            end_color: Color::new(48, 48, 48),
+            duration_ms: NonZeroU32::new(60_000).unwrap(),
+            ..Cue::white_breathing()
+        };
+        let settings: DeviceSettings = DeviceSettings::new();
+        let mut averages = [0; 10];
+        for (step, average) in averages.iter_mut().enumerate() {
+            let time_ms = 5000 + step as u32 * 2000;
+            let mut wide_frame = [WideColor::black(); CHANNELS as usize];
+            fade.render_wide_into(time_ms, &mut wide_frame);
+
+            let mut dither = TemporalDither::new(0);
+            let mut sum = 0;
+            for _ in 0..256 {
+                let mut frame = [Color::black(); CHANNELS as usize];
+                settings.apply_frame_dithered(&wide_frame, &mut frame, &dither);
+                sum += <[u8; 3]>::from(frame[0])[0] as u32;
+                dither.next_frame();
+            }
+            let expected = settings.output.apply_wide_precise(wide_frame[0])[0];
+            assert_eq!(sum, expected.to_bits() as u32, "{} ms", time_ms);
+            *average = sum;
+        }
+        // The average still gets brighter all the time
+        assert!(averages[9] < 256);
+        for pair in averages.windows(2) {
+            assert!(pair[0] < pair[1], "{:?}", averages);
+        }
+    }
+}
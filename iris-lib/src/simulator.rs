@@ -0,0 +1,194 @@
+use crate::color::Color;
+use crate::cue::{Cue, CHANNELS};
+use crate::protocol::{
+    Command, DeviceError, Identity, Parser, Response, MAX_MESSAGE_LEN, PROTOCOL_VERSION,
+};
+use crate::render::Render;
+use crate::settings::DeviceSettings;
+use crate::sysex::BROADCAST;
+
+/// Number of slots of a [`Simulator`] if not specified otherwise
+pub const DEFAULT_SLOTS: usize = 8;
+
+/// Firmware version a [`Simulator`] reports
+pub const FIRMWARE_VERSION: [u8; 3] = [0, 1, 0];
+
+/// A virtual Iris device that stores and plays Cues in `SLOTS` slots. It
+/// speaks the SysEx protocol of [`crate::protocol`] just like the firmware,
+/// so Iris Hub can be tested without any hardware.
+/// # Examples
+/// ```
+/// use iris_lib::cue::Cue;
+/// use iris_lib::protocol::{Command, Parser, Response};
+/// use iris_lib::render::Render;
+/// use iris_lib::simulator::Simulator;
+///
+/// let mut device: Simulator = Simulator::new(0x01);
+/// let mut message = [0; 64];
+/// let commands: [Command; 2] = [
+///     Command::UploadCue { slot: 0, cue: Cue::rainbow() },
+///     Command::LaunchSlot { slot: 0 },
+/// ];
+/// let mut parser = Parser::new();
+/// for command in commands.iter() {
+///     let len = command.encode_sysex(0x01, &mut message).unwrap();
+///     device.receive(&message[..len], |response| {
+///         let parsed = response.iter().find_map(|byte| parser.push_response(*byte));
+///         assert_eq!(parsed, Some(Ok((0x01, Response::<12>::Ack))));
+///     });
+/// }
+///
+/// device.advance(500);
+/// let mut expected = Cue::rainbow().render_frame(500);
+/// device.settings().apply_frame(&mut expected);
+/// assert_eq!(device.frame(), expected);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Simulator<const N: usize = { CHANNELS as usize }, const SLOTS: usize = DEFAULT_SLOTS> {
+    device_id: u8,
+    slots: [Option<Cue<N>>; SLOTS],
+    /// Slot of the Cue that is playing
+    launched: Option<u8>,
+    /// Time since the device was started
+    time_ms: u32,
+    /// Time at which the playing Cue was launched
+    launch_time_ms: u32,
+    settings: DeviceSettings<N>,
+    parser: Parser,
+}
+
+impl<const N: usize, const SLOTS: usize> Simulator<N, SLOTS> {
+    /// Fails compilation if the slots can't be addressed with a u8
+    const VALID_SLOTS: () = assert!(
+        SLOTS <= u8::MAX as usize,
+        "A Simulator can have at most 255 slots"
+    );
+
+    /// Device with empty slots, which responds to commands sent to
+    /// `device_id` or [`BROADCAST`]
+    pub fn new(device_id: u8) -> Simulator<N, SLOTS> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID_SLOTS;
+        Simulator {
+            device_id,
+            slots: core::array::from_fn(|_| None),
+            launched: None,
+            time_ms: 0,
+            launch_time_ms: 0,
+            settings: DeviceSettings::default(),
+            parser: Parser::new(),
+        }
+    }
+
+    /// Read MIDI bytes sent to the device, which may contain any part of a
+    /// message. Every command addressed to this device is executed once it is
+    /// complete, and `respond` is called with the SysEx message of the
+    /// response. Messages that are cut off or that aren't SysEx messages of
+    /// this manufacturer are ignored, as their device ID is unknown.
+    pub fn receive(&mut self, bytes: &[u8], mut respond: impl FnMut(&[u8])) {
+        for byte in bytes {
+            let (device_id, command) = match self.parser.push(*byte) {
+                Some(Ok((device_id, data))) => (device_id, Command::decode(data)),
+                _ => continue,
+            };
+            if device_id != self.device_id && device_id != BROADCAST {
+                continue;
+            }
+            let response = match command {
+                Ok(command) => self.execute(command),
+                Err(error) => Response::Error(error.into()),
+            };
+            let mut message = [0; MAX_MESSAGE_LEN];
+            // Every response fits into MAX_MESSAGE_LEN
+            if let Ok(len) = response.encode_sysex(self.device_id, &mut message) {
+                respond(&message[..len]);
+            }
+        }
+    }
+
+    /// Execute a command like the firmware would after receiving it
+    pub fn execute(&mut self, command: Command<N>) -> Response<N> {
+        match self.try_execute(command) {
+            Ok(response) => response,
+            Err(error) => Response::Error(error),
+        }
+    }
+
+    fn try_execute(&mut self, command: Command<N>) -> Result<Response<N>, DeviceError> {
+        match command {
+            Command::Identify => {
+                return Ok(Response::Identity(Identity {
+                    protocol_version: PROTOCOL_VERSION,
+                    firmware_version: FIRMWARE_VERSION,
+                    channels: N as u8,
+                    slots: SLOTS as u8,
+                }))
+            }
+            Command::UploadCue { slot, cue } => *self.slot_mut(slot)? = Some(cue),
+            Command::DeleteSlot { slot } => {
+                *self.slot_mut(slot)? = None;
+                // Don't keep playing a Cue that was deleted
+                if self.launched == Some(slot) {
+                    self.launched = None;
+                }
+            }
+            Command::LaunchSlot { slot } => {
+                self.stored_cue(slot)?;
+                self.launched = Some(slot);
+                self.launch_time_ms = self.time_ms;
+            }
+            Command::ReadSlot { slot } => {
+                let cue = self.stored_cue(slot)?.clone();
+                return Ok(Response::Cue { slot, cue });
+            }
+            Command::SetBrightness(brightness) => self.settings.output.set_brightness(brightness),
+        }
+        Ok(Response::Ack)
+    }
+
+    /// The Cue stored in a slot, if any
+    pub fn slot(&self, slot: u8) -> Option<&Cue<N>> {
+        self.slots.get(slot as usize)?.as_ref()
+    }
+
+    /// Slot of the Cue that is playing
+    pub fn launched_slot(&self) -> Option<u8> {
+        self.launched
+    }
+
+    pub fn settings(&self) -> &DeviceSettings<N> {
+        &self.settings
+    }
+
+    /// Let time pass on the device
+    pub fn advance(&mut self, elapsed_ms: u32) {
+        self.time_ms = self.time_ms.wrapping_add(elapsed_ms);
+    }
+
+    /// The colors the LEDs currently show, after the [`DeviceSettings`] are
+    /// applied. Cues start at time 0 when they are launched. All LEDs are
+    /// black if no Cue is playing.
+    pub fn frame(&self) -> [Color; N] {
+        let mut frame = [Color::black(); N];
+        if let Some(cue) = self.launched.and_then(|slot| self.slot(slot)) {
+            let time_ms = self.time_ms.wrapping_sub(self.launch_time_ms);
+            cue.render_into(time_ms, &mut frame);
+            self.settings.apply_frame(&mut frame);
+        }
+        frame
+    }
+
+    fn slot_mut(&mut self, slot: u8) -> Result<&mut Option<Cue<N>>, DeviceError> {
+        self.slots
+            .get_mut(slot as usize)
+            .ok_or(DeviceError::InvalidSlot(slot))
+    }
+
+    fn stored_cue(&self, slot: u8) -> Result<&Cue<N>, DeviceError> {
+        self.slots
+            .get(slot as usize)
+            .ok_or(DeviceError::InvalidSlot(slot))?
+            .as_ref()
+            .ok_or(DeviceError::EmptySlot(slot))
+    }
+}
@@ -0,0 +1,414 @@
+//! MIDI System Exclusive messages, used to transfer data between Iris Hub and
+//! the hardware. A message has the following layout:
+//! - [`START`] (`0xF0`)
+//! - [`MANUFACTURER_ID`]
+//! - Device ID, or [`BROADCAST`] to address all devices
+//! - The data, packed into 7 bit bytes, see [`pack`]
+//! - [`END`] (`0xF7`)
+
+use crate::cue::Cue;
+use crate::encoding::{DecodeError, MAX_ENCODED_LEN};
+use core::fmt;
+
+/// Status byte that starts every SysEx message
+pub const START: u8 = 0xF0;
+/// Status byte that ends every SysEx message
+pub const END: u8 = 0xF7;
+/// ID the MIDI Manufacturers Association reserves for non-commercial use
+pub const MANUFACTURER_ID: u8 = 0x7D;
+/// Device ID every device responds to
+pub const BROADCAST: u8 = 0x7F;
+
+/// Start, manufacturer ID and device ID
+const HEADER_LEN: usize = 3;
+
+/// Reasons why a SysEx message couldn't be written or read
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SysExError {
+    /// The output buffer can't hold the result
+    BufferTooSmall { needed: usize },
+    /// Device IDs have to fit into 7 bits
+    InvalidDeviceId(u8),
+    /// The message doesn't begin with [`START`]
+    MissingStart,
+    /// The message doesn't finish with [`END`]
+    MissingEnd,
+    /// The message ends before the device ID
+    TooShort,
+    /// The message is meant for devices of another manufacturer
+    WrongManufacturer(u8),
+    /// The packed data contains a byte with the highest bit set
+    InvalidDataByte(u8),
+    /// The packed data ends with the high bits of a group, but no data
+    InvalidLength,
+    /// The data is not a valid encoded Cue
+    Cue(DecodeError),
+}
+
+impl fmt::Display for SysExError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SysExError::BufferTooSmall { needed } => {
+                write!(f, "buffer is too small, {} bytes are needed", needed)
+            }
+            SysExError::InvalidDeviceId(id) => write!(f, "device ID {} has more than 7 bits", id),
+            SysExError::MissingStart => write!(f, "message doesn't start with 0xF0"),
+            SysExError::MissingEnd => write!(f, "message doesn't end with 0xF7"),
+            SysExError::TooShort => write!(f, "message is too short"),
+            SysExError::WrongManufacturer(id) => {
+                write!(f, "message is meant for manufacturer {:#04X}", id)
+            }
+            SysExError::InvalidDataByte(byte) => write!(f, "invalid data byte {:#04X}", byte),
+            SysExError::InvalidLength => write!(f, "packed data has an invalid length"),
+            SysExError::Cue(error) => write!(f, "invalid Cue: {}", error),
+        }
+    }
+}
+
+impl From<DecodeError> for SysExError {
+    fn from(error: DecodeError) -> SysExError {
+        SysExError::Cue(error)
+    }
+}
+
+/// Number of bytes `len` bytes of data take up after packing
+pub const fn packed_len(len: usize) -> usize {
+    len + len.div_ceil(7)
+}
+
+/// Pack 8 bit data into bytes with the highest bit cleared, as only those may
+/// appear in a SysEx message. Every group of up to 7 bytes is preceded by a
+/// byte holding their highest bits, the first byte of the group in the lowest
+/// bit. Returns the number of bytes written.
+/// # Examples
+/// ```
+/// use iris_lib::sysex::{pack, unpack};
+///
+/// let data = [0x80, 0x01, 0xFF];
+/// let mut packed = [0; 4];
+/// assert_eq!(pack(&data, &mut packed), Ok(4));
+/// assert_eq!(packed, [0b101, 0x00, 0x01, 0x7F]);
+///
+/// let mut unpacked = [0; 3];
+/// assert_eq!(unpack(&packed, &mut unpacked), Ok(3));
+/// assert_eq!(unpacked, data);
+/// ```
+pub fn pack(data: &[u8], packed: &mut [u8]) -> Result<usize, SysExError> {
+    let needed = packed_len(data.len());
+    if packed.len() < needed {
+        return Err(SysExError::BufferTooSmall { needed });
+    }
+    for (group, output) in data.chunks(7).zip(packed.chunks_mut(8)) {
+        output[0] = group
+            .iter()
+            .enumerate()
+            .fold(0, |high_bits, (bit, byte)| high_bits | (byte >> 7) << bit);
+        for (output, byte) in output[1..].iter_mut().zip(group) {
+            *output = byte & 0x7F;
+        }
+    }
+    Ok(needed)
+}
+
+/// Reverse [`pack`] and return the number of bytes written
+pub fn unpack(packed: &[u8], data: &mut [u8]) -> Result<usize, SysExError> {
+    if let Some(byte) = packed.iter().find(|byte| **byte > 0x7F) {
+        return Err(SysExError::InvalidDataByte(*byte));
+    }
+    if packed.len() % 8 == 1 {
+        return Err(SysExError::InvalidLength);
+    }
+    let needed = packed.len() - packed.len().div_ceil(8);
+    if data.len() < needed {
+        return Err(SysExError::BufferTooSmall { needed });
+    }
+    for (group, output) in packed.chunks(8).zip(data.chunks_mut(7)) {
+        let high_bits = group[0];
+        for (bit, (output, byte)) in output.iter_mut().zip(&group[1..]).enumerate() {
+            *output = byte | (high_bits >> bit & 1) << 7;
+        }
+    }
+    Ok(needed)
+}
+
+/// Write a complete SysEx message containing `data` to the start of the
+/// buffer and return its length
+pub fn write_message(device_id: u8, data: &[u8], buffer: &mut [u8]) -> Result<usize, SysExError> {
+    if device_id > 0x7F {
+        return Err(SysExError::InvalidDeviceId(device_id));
+    }
+    let needed = HEADER_LEN + packed_len(data.len()) + 1;
+    if buffer.len() < needed {
+        return Err(SysExError::BufferTooSmall { needed });
+    }
+    buffer[..HEADER_LEN].copy_from_slice(&[START, MANUFACTURER_ID, device_id]);
+    pack(data, &mut buffer[HEADER_LEN..needed - 1])?;
+    buffer[needed - 1] = END;
+    Ok(needed)
+}
+
+/// Like [`write_message`], but `write` writes the data into the buffer itself,
+/// which is then packed in place. This saves a second buffer for the data.
+/// `write` gets the space the data may take up so the message still fits, and
+/// reports a lack of space as [`SysExError::BufferTooSmall`] with the length
+/// of the data, which is turned into the length of the message.
+pub(crate) fn write_message_with(
+    device_id: u8,
+    buffer: &mut [u8],
+    write: impl FnOnce(&mut [u8]) -> Result<usize, SysExError>,
+) -> Result<usize, SysExError> {
+    if device_id > 0x7F {
+        return Err(SysExError::InvalidDeviceId(device_id));
+    }
+    let message_len = |len| HEADER_LEN + packed_len(len) + 1;
+    let space = buffer.len().saturating_sub(HEADER_LEN + 1);
+    // The longest data whose packed form fits, placed at the end of the
+    // buffer. Packing starts at the front, and never catches up with the data
+    // that is still to be read.
+    let offset = buffer.len() - (space - space.div_ceil(8));
+    let len = write(&mut buffer[offset..]).map_err(|error| match error {
+        SysExError::BufferTooSmall { needed } => SysExError::BufferTooSmall {
+            needed: message_len(needed),
+        },
+        error => error,
+    })?;
+    // Even empty data needs the space for the header and end
+    let needed = message_len(len);
+    if buffer.len() < needed {
+        return Err(SysExError::BufferTooSmall { needed });
+    }
+    for group in 0..len.div_ceil(7) {
+        let start = offset + group * 7;
+        let end = (start + 7).min(offset + len);
+        let mut bytes = [0; 7];
+        bytes[..end - start].copy_from_slice(&buffer[start..end]);
+        let output = HEADER_LEN + group * 8;
+        pack(
+            &bytes[..end - start],
+            &mut buffer[output..output + 1 + end - start],
+        )?;
+    }
+    buffer[..HEADER_LEN].copy_from_slice(&[START, MANUFACTURER_ID, device_id]);
+    buffer[needed - 1] = END;
+    Ok(needed)
+}
+
+/// Read a complete SysEx message written by [`write_message`], unpacking its
+/// data into `data`. Returns the device ID and the length of the data.
+/// # Examples
+/// ```
+/// use iris_lib::sysex::{read_message, write_message, SysExError};
+///
+/// let mut message = [0; 9];
+/// assert_eq!(write_message(5, b"Iris", &mut message), Ok(9));
+///
+/// let mut data = [0; 4];
+/// assert_eq!(read_message(&message, &mut data), Ok((5, 4)));
+/// assert_eq!(&data, b"Iris");
+///
+/// let other_manufacturer = [0xF0, 0x41, 0x10, 0x00, 0xF7];
+/// assert_eq!(
+///     read_message(&other_manufacturer, &mut data),
+///     Err(SysExError::WrongManufacturer(0x41))
+/// );
+/// ```
+pub fn read_message(message: &[u8], data: &mut [u8]) -> Result<(u8, usize), SysExError> {
+    match message.first() {
+        Some(&START) => {}
+        _ => return Err(SysExError::MissingStart),
+    }
+    let body = match message[1..].split_last() {
+        Some((&END, body)) => body,
+        _ => return Err(SysExError::MissingEnd),
+    };
+    let (manufacturer, device_id, packed) = match body {
+        [manufacturer, device_id, packed @ ..] => (*manufacturer, *device_id, packed),
+        _ => return Err(SysExError::TooShort),
+    };
+    if manufacturer != MANUFACTURER_ID {
+        return Err(SysExError::WrongManufacturer(manufacturer));
+    }
+    if device_id > 0x7F {
+        return Err(SysExError::InvalidDataByte(device_id));
+    }
+    let len = unpack(packed, data)?;
+    Ok((device_id, len))
+}
+
+impl<const N: usize> Cue<N> {
+    /// Write a SysEx message containing the Cue in the binary encoding of
+    /// [`crate::encoding`] and return its length
+    /// # Examples
+    /// ```
+    /// use iris_lib::cue::Cue;
+    /// use iris_lib::sysex::BROADCAST;
+    ///
+    /// let cue: Cue = Cue::rainbow();
+    /// let mut message = [0; 64];
+    /// let len = cue.encode_sysex(BROADCAST, &mut message).unwrap();
+    /// assert!(message[1..len - 1].iter().all(|byte| *byte < 0x80));
+    /// assert_eq!(Cue::decode_sysex(&message[..len]), Ok((BROADCAST, cue)));
+    /// ```
+    pub fn encode_sysex(&self, device_id: u8, buffer: &mut [u8]) -> Result<usize, SysExError> {
+        let mut encoded = [0; MAX_ENCODED_LEN];
+        let len = self
+            .encode_into(&mut encoded)
+            .map_err(|error| SysExError::BufferTooSmall {
+                needed: error.needed,
+            })?;
+        write_message(device_id, &encoded[..len], buffer)
+    }
+
+    /// Read a Cue written by [`Cue::encode_sysex`], together with the device
+    /// ID the message was sent to
+    pub fn decode_sysex(message: &[u8]) -> Result<(u8, Cue<N>), SysExError> {
+        let mut encoded = [0; MAX_ENCODED_LEN];
+        let (device_id, len) = match read_message(message, &mut encoded) {
+            // Longer than any Cue
+            Err(SysExError::BufferTooSmall { .. }) => return Err(SysExError::InvalidLength),
+            result => result?,
+        };
+        Ok((device_id, Cue::decode(&encoded[..len])?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sysex::*;
+
+    #[test]
+    fn pack_round_trip() {
+        let mut data = [0; 64];
+        for (index, byte) in data.iter_mut().enumerate() {
+            *byte = (index as u8).wrapping_mul(73);
+        }
+        for len in 0..data.len() {
+            let mut packed = [0; 74];
+            let packed_len = pack(&data[..len], &mut packed).unwrap();
+            assert_eq!(packed_len, len + len.div_ceil(7));
+            assert!(packed.iter().all(|byte| *byte < 0x80));
+
+            let mut unpacked = [0; 64];
+            assert_eq!(unpack(&packed[..packed_len], &mut unpacked), Ok(len));
+            assert_eq!(unpacked[..len], data[..len]);
+        }
+
+        assert_eq!(
+            pack(&data[..8], &mut [0; 9]),
+            Err(SysExError::BufferTooSmall { needed: 10 })
+        );
+        assert_eq!(
+            unpack(&[0, 1, 2], &mut [0; 1]),
+            Err(SysExError::BufferTooSmall { needed: 2 })
+        );
+        assert_eq!(
+            unpack(&[0, 1, 2, 3, 4, 5, 6, 7, 0], &mut data),
+            Err(SysExError::InvalidLength)
+        );
+        assert_eq!(
+            unpack(&[0, 1, 0x80], &mut data),
+            Err(SysExError::InvalidDataByte(0x80))
+        );
+    }
+
+    #[test]
+    fn write_in_place() {
+        let mut data = [0; 40];
+        for (index, byte) in data.iter_mut().enumerate() {
+            *byte = (index as u8).wrapping_mul(151);
+        }
+        let write = |len: usize| {
+            move |space: &mut [u8]| {
+                if space.len() < len {
+                    return Err(SysExError::BufferTooSmall { needed: len });
+                }
+                space[..len].copy_from_slice(&data[..len]);
+                Ok(len)
+            }
+        };
+        for len in 0..data.len() {
+            let mut expected = [0; 56];
+            let expected_len = write_message(0x12, &data[..len], &mut expected).unwrap();
+            for buffer_len in 0..expected.len() {
+                let mut message = [0xFF; 56];
+                let result = write_message_with(0x12, &mut message[..buffer_len], write(len));
+                if buffer_len < expected_len {
+                    let needed = expected_len;
+                    assert_eq!(result, Err(SysExError::BufferTooSmall { needed }));
+                } else {
+                    assert_eq!(result, Ok(expected_len));
+                    assert_eq!(message[..expected_len], expected[..expected_len]);
+                }
+            }
+        }
+        assert_eq!(
+            write_message_with(0x80, &mut [0; 8], write(1)),
+            Err(SysExError::InvalidDeviceId(0x80))
+        );
+    }
+
+    #[test]
+    fn framing() {
+        let mut message = [0; 16];
+        let len = write_message(0x12, &[0xF0, 0xF7, 0x00], &mut message).unwrap();
+        assert_eq!(
+            message[..len],
+            [0xF0, 0x7D, 0x12, 0b011, 0x70, 0x77, 0x00, 0xF7]
+        );
+        assert_eq!(
+            write_message(0x80, &[], &mut message),
+            Err(SysExError::InvalidDeviceId(0x80))
+        );
+        assert_eq!(
+            write_message(0, &[1], &mut message[..5]),
+            Err(SysExError::BufferTooSmall { needed: 6 })
+        );
+
+        let read = |message: &[u8]| read_message(message, &mut [0; 16]);
+        assert_eq!(read(&[0xF0, 0x7D, 0x00, 0xF7]), Ok((0, 0)));
+        assert_eq!(read(&[]), Err(SysExError::MissingStart));
+        assert_eq!(read(&[0x7D, 0x00, 0xF7]), Err(SysExError::MissingStart));
+        assert_eq!(read(&[0xF0]), Err(SysExError::MissingEnd));
+        assert_eq!(read(&[0xF0, 0x7D, 0x00]), Err(SysExError::MissingEnd));
+        assert_eq!(read(&[0xF0, 0x7D, 0xF7]), Err(SysExError::TooShort));
+        assert_eq!(
+            read(&[0xF0, 0x7D, 0xF0, 0xF7]),
+            Err(SysExError::InvalidDataByte(0xF0))
+        );
+        assert_eq!(
+            read(&[0xF0, 0x7D, 0x00, 0x00, 0x90, 0xF7]),
+            Err(SysExError::InvalidDataByte(0x90))
+        );
+    }
+
+    #[test]
+    fn cues() {
+        let cue: Cue<16> = Cue::sunset();
+        let mut message = [0; 128];
+        let len = cue.encode_sysex(0x01, &mut message).unwrap();
+        assert_eq!(Cue::decode_sysex(&message[..len]), Ok((0x01, cue.clone())));
+        assert_eq!(
+            Cue::<12>::decode_sysex(&message[..len]),
+            Err(SysExError::Cue(DecodeError::ChannelCount(16)))
+        );
+        assert_eq!(
+            cue.encode_sysex(0x01, &mut message[..len - 1]),
+            Err(SysExError::BufferTooSmall { needed: len })
+        );
+
+        // A single corrupted bit is detected
+        message[10] ^= 0x04;
+        assert_eq!(
+            Cue::<16>::decode_sysex(&message[..len]),
+            Err(SysExError::Cue(DecodeError::ChecksumMismatch))
+        );
+
+        // Data longer than any Cue
+        let mut long = [0; 512];
+        let len = write_message(0x01, &[0; 400], &mut long).unwrap();
+        assert_eq!(
+            Cue::<16>::decode_sysex(&long[..len]),
+            Err(SysExError::InvalidLength)
+        );
+    }
+}
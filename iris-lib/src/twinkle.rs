@@ -0,0 +1,149 @@
+use crate::color::Color;
+use crate::cue::Fraction;
+use crate::prng::Prng;
+use core::num::NonZeroU16;
+use fixed::types::U0F8;
+use serde::{Deserialize, Serialize};
+
+/// LEDs flash up to a highlight color at random and fade back to the color of
+/// the Cue. The pattern only depends on the seed, the time and the LED, so
+/// every frame can be calculated on its own and is identical on every device.
+///
+/// Time is divided into intervals of `decay_ms`. In each interval, every LED
+/// flashes at most once, at a random point in time.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Twinkle {
+    /// Different seeds produce different patterns
+    pub seed: u32,
+    /// The color the LEDs flash to
+    pub color: Color,
+    /// Chance that an LED flashes during each interval, between 0 and 1
+    pub density: Fraction,
+    /// Time until a flash has faded out completely
+    pub decay_ms: NonZeroU16,
+}
+
+impl Twinkle {
+    /// How far a single LED is lit by a flash, 0 being not at all
+    /// # Examples
+    /// ```
+    /// use iris_lib::color::Color;
+    /// use iris_lib::twinkle::Twinkle;
+    /// use core::num::NonZeroU16;
+    /// use fixed::types::U0F8;
+    ///
+    /// let twinkle = Twinkle {
+    ///     seed: 1,
+    ///     color: Color::white(),
+    ///     density: 0.0.into(),
+    ///     decay_ms: NonZeroU16::new(500).unwrap(),
+    /// };
+    /// assert_eq!(twinkle.intensity(1234, 5), U0F8::MIN);
+    /// ```
+    pub fn intensity(&self, time_ms: u32, channel: u8) -> U0F8 {
+        let decay = self.decay_ms.get() as u32;
+        let interval = time_ms / decay;
+        let prng = Prng::new(self.seed).derive(channel as u32);
+
+        // A flash is as long as an interval, so only flashes of the current
+        // and the previous interval can be visible
+        let current = self.flash(&prng, interval, time_ms);
+        let previous = interval
+            .checked_sub(1)
+            .map_or(U0F8::MIN, |interval| self.flash(&prng, interval, time_ms));
+        current.max(previous)
+    }
+
+    /// Mix `color` with the highlight color according to [`Twinkle::intensity`]
+    pub fn apply(&self, color: Color, time_ms: u32, channel: u8) -> Color {
+        color.linear_mix_rgb(&self.color, self.intensity(time_ms, channel))
+    }
+
+    /// Intensity of the flash that starts during `interval`, if there is one
+    fn flash(&self, prng: &Prng, interval: u32, time_ms: u32) -> U0F8 {
+        let decay = self.decay_ms.get() as u32;
+        let mut prng = prng.derive(interval);
+        if prng.next_below(u8::MAX as u32) >= self.density.0.to_bits() as u32 {
+            return U0F8::MIN;
+        }
+
+        // interval * decay ≤ time_ms, so this can't overflow
+        let since_interval = time_ms - interval * decay;
+        let offset = prng.next_below(decay);
+        if since_interval < offset {
+            return U0F8::MIN;
+        }
+        let elapsed = since_interval - offset;
+        if elapsed >= decay {
+            return U0F8::MIN;
+        }
+
+        // Fade out linearly. Actual formula:
+        // 1 - elapsed / decay
+        let faded = (elapsed * u8::MAX as u32 + decay / 2) / decay;
+        U0F8::from_bits(u8::MAX - faded as u8)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::twinkle::*;
+
+    fn twinkle(density: f32) -> Twinkle {
+        Twinkle {
+            seed: 0xC0FFEE,
+            color: Color::white(),
+            density: density.into(),
+            decay_ms: NonZeroU16::new(400).unwrap(),
+        }
+    }
+
+    #[test]
+    fn deterministic() {
+        let a = twinkle(0.5);
+        let b = twinkle(0.5);
+        let other_seed = Twinkle { seed: 1, ..a };
+        let mut differences = 0;
+        for time_ms in (0..10_000).step_by(7) {
+            for channel in 0..12 {
+                assert_eq!(a.intensity(time_ms, channel), b.intensity(time_ms, channel));
+                if a.intensity(time_ms, channel) != other_seed.intensity(time_ms, channel) {
+                    differences += 1;
+                }
+            }
+        }
+        assert!(differences > 0);
+    }
+
+    #[test]
+    fn density() {
+        let count_flashes = |twinkle: Twinkle| {
+            (0..100 * 400)
+                .step_by(400)
+                .filter(|time_ms| {
+                    (*time_ms..time_ms + 400)
+                        .any(|time_ms| twinkle.intensity(time_ms, 3) == U0F8::MAX)
+                })
+                .count()
+        };
+        assert_eq!(count_flashes(twinkle(0.0)), 0);
+        assert_eq!(count_flashes(twinkle(1.0)), 100);
+        assert!((30..70).contains(&count_flashes(twinkle(0.5))));
+    }
+
+    #[test]
+    fn flashes_decay() {
+        let twinkle = twinkle(0.3);
+        let start = (0..u32::MAX)
+            .find(|time_ms| twinkle.intensity(*time_ms, 0) == U0F8::MAX)
+            .unwrap();
+        // Fades out over the decay time
+        let mut previous = U0F8::MAX;
+        for time_ms in start..start + 400 {
+            let intensity = twinkle.intensity(time_ms, 0);
+            assert!(intensity <= previous || intensity == U0F8::MAX);
+            previous = intensity;
+        }
+        assert_eq!(twinkle.apply(Color::black(), start, 0), Color::white());
+    }
+}
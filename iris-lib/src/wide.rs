@@ -0,0 +1,162 @@
+use crate::color::Color;
+use fixed::types::{U0F16, U0F8};
+use serde::{Deserialize, Serialize};
+
+/// RGB color with 16 bits per component, for LED drivers with a higher
+/// resolution than [`Color`]. Every [`Color`] can be converted into a
+/// WideColor without loss, where 255 becomes 65535.
+/// # Examples
+/// ```
+/// use iris_lib::color::Color;
+/// use iris_lib::wide::WideColor;
+///
+/// let color = Color::new(255, 128, 0);
+/// let wide = WideColor::from(color);
+/// assert_eq!(wide, WideColor::new(65535, 32896, 0));
+/// assert_eq!(Color::from(wide), color);
+/// ```
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Default)]
+pub struct WideColor {
+    red: u16,
+    green: u16,
+    blue: u16,
+}
+
+impl WideColor {
+    pub fn new(red: u16, green: u16, blue: u16) -> WideColor {
+        WideColor { red, green, blue }
+    }
+
+    pub fn black() -> WideColor {
+        WideColor::new(0, 0, 0)
+    }
+
+    pub fn white() -> WideColor {
+        WideColor::new(u16::MAX, u16::MAX, u16::MAX)
+    }
+
+    /// Mix two colors by linearly interpolating R, G and B, see [`interpolate_wide`]
+    pub fn linear_mix_rgb(&self, other: &WideColor, factor: U0F16) -> WideColor {
+        WideColor::new(
+            interpolate_wide(self.red, other.red, factor),
+            interpolate_wide(self.green, other.green, factor),
+            interpolate_wide(self.blue, other.blue, factor),
+        )
+    }
+}
+
+/// Interpolate between two numbers using a 16 bit factor between 0 and 1,
+/// rounded to the nearest integer. Like [`interpolate`](crate::color::interpolate),
+/// [`U0F16::MAX`] returns `end`.
+/// # Examples
+/// ```
+/// use iris_lib::wide::interpolate_wide;
+/// use fixed::types::U0F16;
+/// use fixed_macro::types::U0F16;
+///
+/// assert_eq!(interpolate_wide(0, 1000, U0F16!(0.5)), 500);
+/// assert_eq!(interpolate_wide(1000, 0, U0F16!(0.25)), 750);
+/// assert_eq!(interpolate_wide(12, 60000, U0F16::MAX), 60000);
+/// ```
+pub fn interpolate_wide(start: u16, end: u16, factor: U0F16) -> u16 {
+    let delta = end as i64 - start as i64;
+    let scaled = delta * factor.to_bits() as i64;
+    let offset = (scaled + scaled.signum() * (u16::MAX as i64 / 2)) / u16::MAX as i64;
+    (start as i64 + offset) as u16
+}
+
+/// Extend a factor to 16 bits, so U0F8::MAX becomes U0F16::MAX
+pub fn widen_factor(factor: U0F8) -> U0F16 {
+    U0F16::from_bits(factor.to_bits() as u16 * 257)
+}
+
+/// Evaluate a function that only takes 8 bit factors at a 16 bit factor, by
+/// interpolating between the results of the two closest 8 bit factors
+pub(crate) fn interpolate_steps(factor: U0F16, color_at: impl Fn(U0F8) -> Color) -> WideColor {
+    // u16::MAX = 257 * u8::MAX, so every step of the 8 bit factor is 257 wide
+    let step = (factor.to_bits() / 257) as u8;
+    let remainder = factor.to_bits() % 257;
+    let lower = WideColor::from(color_at(U0F8::from_bits(step)));
+    if remainder == 0 {
+        return lower;
+    }
+    // step < u8::MAX, as remainder would be 0 otherwise
+    let upper = WideColor::from(color_at(U0F8::from_bits(step + 1)));
+    lower.linear_mix_rgb(&upper, U0F16::from_bits(remainder * u8::MAX as u16))
+}
+
+impl From<Color> for WideColor {
+    fn from(color: Color) -> WideColor {
+        let [red, green, blue]: [u8; 3] = color.into();
+        // 257 * 255 = 65535, so this maps the full range onto the full range
+        WideColor::new(red as u16 * 257, green as u16 * 257, blue as u16 * 257)
+    }
+}
+
+impl From<WideColor> for Color {
+    /// Rounds to the nearest 8 bit value
+    fn from(color: WideColor) -> Color {
+        let narrow = |value: u16| ((value as u32 * u8::MAX as u32 + 0x7FFF) / 0xFFFF) as u8;
+        Color::new(narrow(color.red), narrow(color.green), narrow(color.blue))
+    }
+}
+
+impl From<WideColor> for [u16; 3] {
+    fn from(color: WideColor) -> [u16; 3] {
+        [color.red, color.green, color.blue]
+    }
+}
+
+impl From<[u16; 3]> for WideColor {
+    fn from(arr: [u16; 3]) -> WideColor {
+        WideColor::new(arr[0], arr[1], arr[2])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::wide::*;
+
+    #[test]
+    fn lossless_round_trip() {
+        for value in 0..=u8::MAX {
+            let color = Color::new(value, u8::MAX - value, value / 3);
+            assert_eq!(Color::from(WideColor::from(color)), color);
+        }
+        for value in 0..=u16::MAX {
+            let wide = WideColor::new(value, value, value);
+            let [narrow, _, _]: [u8; 3] = Color::from(wide).into();
+            let error = (narrow as i32 * 257 - value as i32).abs();
+            assert!(error <= 257 / 2, "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_interpolate_wide() {
+        assert_eq!(interpolate_wide(0, u16::MAX, U0F16::MIN), 0);
+        assert_eq!(interpolate_wide(0, u16::MAX, U0F16::MAX), u16::MAX);
+        assert_eq!(interpolate_wide(u16::MAX, 0, U0F16::MAX), 0);
+        // Matches the 8 bit version for widened values
+        for factor in 0..=u8::MAX {
+            let factor = U0F8::from_bits(factor);
+            let wide = interpolate_wide(0, 200 * 257, widen_factor(factor));
+            let narrow = crate::color::interpolate(0, 200, factor);
+            assert!((wide as i32 - narrow as i32 * 257).abs() < 257);
+        }
+    }
+
+    #[test]
+    fn steps() {
+        let color_at = |factor: U0F8| Color::new(factor.to_bits(), 0, 0);
+        assert_eq!(interpolate_steps(U0F16::MIN, color_at), WideColor::black());
+        assert_eq!(
+            interpolate_steps(U0F16::MAX, color_at),
+            WideColor::new(u16::MAX, 0, 0)
+        );
+        // Between the steps, the result is interpolated
+        for bits in 0..=u16::MAX {
+            let wide = interpolate_steps(U0F16::from_bits(bits), color_at);
+            assert_eq!(wide, WideColor::new(bits, 0, 0));
+        }
+    }
+}
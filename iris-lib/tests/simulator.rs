@@ -0,0 +1,262 @@
+//! Drives a simulated device through its SysEx interface, like Iris Hub does
+
+use iris_lib::color::Color;
+use iris_lib::cue::Cue;
+use iris_lib::protocol::{
+    Command, DeviceError, Parser, ProtocolError, Response, MAX_MESSAGE_LEN, PROTOCOL_VERSION,
+};
+use iris_lib::render::Render;
+use iris_lib::simulator::{Simulator, FIRMWARE_VERSION};
+use iris_lib::sysex::{self, BROADCAST};
+
+const DEVICE_ID: u8 = 0x03;
+
+/// Host side of the connection to a simulated device
+struct Host {
+    device: Simulator<16, 4>,
+    parser: Parser,
+}
+
+impl Host {
+    fn new() -> Host {
+        Host {
+            device: Simulator::new(DEVICE_ID),
+            parser: Parser::new(),
+        }
+    }
+
+    /// Send raw MIDI bytes and collect the responses
+    fn send_bytes(&mut self, bytes: &[u8]) -> Vec<Result<(u8, Response<16>), ProtocolError>> {
+        let parser = &mut self.parser;
+        let mut responses = Vec::new();
+        self.device.receive(bytes, |message| {
+            responses.extend(
+                message
+                    .iter()
+                    .filter_map(|byte| parser.push_response(*byte)),
+            )
+        });
+        responses
+    }
+
+    fn send_to(&mut self, device_id: u8, command: Command<16>) -> Option<Response<16>> {
+        let mut message = [0; MAX_MESSAGE_LEN];
+        let len = command.encode_sysex(device_id, &mut message).unwrap();
+        let mut responses = self.send_bytes(&message[..len]);
+        assert!(responses.len() <= 1);
+        responses.pop().map(|response| {
+            let (responding_id, response) = response.unwrap();
+            assert_eq!(responding_id, DEVICE_ID);
+            response
+        })
+    }
+
+    fn send(&mut self, command: Command<16>) -> Response<16> {
+        self.send_to(DEVICE_ID, command).unwrap()
+    }
+}
+
+fn expected_frame(device: &Simulator<16, 4>, cue: &Cue<16>, time_ms: u32) -> [Color; 16] {
+    let mut frame = cue.render_frame(time_ms);
+    device.settings().apply_frame(&mut frame);
+    frame
+}
+
+#[test]
+fn identify() {
+    let mut host = Host::new();
+    match host.send(Command::Identify) {
+        Response::Identity(identity) => {
+            assert_eq!(identity.protocol_version, PROTOCOL_VERSION);
+            assert_eq!(identity.firmware_version, FIRMWARE_VERSION);
+            assert_eq!(identity.channels, 16);
+            assert_eq!(identity.slots, 4);
+        }
+        response => panic!("unexpected response {:?}", response),
+    }
+}
+
+#[test]
+fn upload_launch_and_render() {
+    let mut host = Host::new();
+    assert_eq!(host.device.frame(), [Color::black(); 16]);
+
+    let rainbow: Cue<16> = Cue::rainbow();
+    let sunset: Cue<16> = Cue::sunset();
+    let upload = |slot, cue: &Cue<16>| Command::UploadCue {
+        slot,
+        cue: cue.clone(),
+    };
+    assert_eq!(host.send(upload(0, &rainbow)), Response::Ack);
+    assert_eq!(host.send(upload(3, &sunset)), Response::Ack);
+    assert_eq!(host.device.slot(3), Some(&sunset));
+    assert_eq!(host.device.launched_slot(), None);
+
+    // Cues start playing when they are launched
+    host.device.advance(10_000);
+    assert_eq!(host.send(Command::LaunchSlot { slot: 0 }), Response::Ack);
+    for time_ms in (0..6000).step_by(250) {
+        assert_eq!(
+            host.device.frame(),
+            expected_frame(&host.device, &rainbow, time_ms)
+        );
+        host.device.advance(250);
+    }
+
+    assert_eq!(host.send(Command::LaunchSlot { slot: 3 }), Response::Ack);
+    assert_eq!(host.device.launched_slot(), Some(3));
+    host.device.advance(1234);
+    assert_eq!(
+        host.device.frame(),
+        expected_frame(&host.device, &sunset, 1234)
+    );
+
+    // Read back what was uploaded
+    assert_eq!(
+        host.send(Command::ReadSlot { slot: 0 }),
+        Response::Cue {
+            slot: 0,
+            cue: rainbow,
+        }
+    );
+
+    // Deleting the playing Cue stops it
+    assert_eq!(host.send(Command::DeleteSlot { slot: 3 }), Response::Ack);
+    assert_eq!(host.device.launched_slot(), None);
+    assert_eq!(host.device.frame(), [Color::black(); 16]);
+}
+
+#[test]
+fn brightness() {
+    let mut host = Host::new();
+    let cue: Cue<16> = Cue {
+        start_color: Color::white(),
+        end_color: Color::white(),
+        ..Default::default()
+    };
+    host.send(Command::UploadCue { slot: 1, cue });
+    host.send(Command::LaunchSlot { slot: 1 });
+    assert_eq!(host.device.frame(), [Color::white(); 16]);
+
+    assert_eq!(host.send(Command::SetBrightness(0.5.into())), Response::Ack);
+    assert_eq!(host.device.settings().output.brightness(), 0.5.into());
+    assert_eq!(host.device.frame(), [Color::new(128, 128, 128); 16]);
+}
+
+#[test]
+fn errors() {
+    let mut host = Host::new();
+    assert_eq!(
+        host.send(Command::LaunchSlot { slot: 2 }),
+        Response::Error(DeviceError::EmptySlot(2))
+    );
+    assert_eq!(
+        host.send(Command::ReadSlot { slot: 4 }),
+        Response::Error(DeviceError::InvalidSlot(4))
+    );
+    assert_eq!(
+        host.send(Command::UploadCue {
+            slot: 200,
+            cue: Cue::rainbow(),
+        }),
+        Response::Error(DeviceError::InvalidSlot(200))
+    );
+
+    // Commands the device can't read
+    let mut message = [0; MAX_MESSAGE_LEN];
+    let mut send_data = |host: &mut Host, data: &[u8]| {
+        let len = sysex::write_message(DEVICE_ID, data, &mut message).unwrap();
+        host.send_bytes(&message[..len])
+    };
+    assert_eq!(
+        send_data(&mut host, &[0x7E]),
+        [Ok((
+            DEVICE_ID,
+            Response::Error(DeviceError::UnknownCommand(0x7E))
+        ))]
+    );
+    assert_eq!(
+        send_data(&mut host, &[0x04]),
+        [Ok((
+            DEVICE_ID,
+            Response::Error(DeviceError::MalformedCommand)
+        ))]
+    );
+    // A Cue for a device with fewer LEDs
+    let mut data = [0; 64];
+    data[0] = 0x02;
+    let len = 2 + Cue::<12>::rainbow().encode_into(&mut data[2..]).unwrap();
+    assert_eq!(
+        send_data(&mut host, &data[..len]),
+        [Ok((DEVICE_ID, Response::Error(DeviceError::InvalidCue)))]
+    );
+
+    // Messages that are cut off are never answered
+    assert_eq!(
+        host.send_bytes(&[0xF0, 0x7D, DEVICE_ID, 0x00, 0x01, 0x90, 0x3C, 0x40]),
+        []
+    );
+    // The device is still working
+    assert!(matches!(
+        host.send(Command::Identify),
+        Response::Identity(_)
+    ));
+}
+
+#[test]
+fn addressing() {
+    let mut host = Host::new();
+    assert_eq!(
+        host.send_to(BROADCAST, Command::LaunchSlot { slot: 0 }),
+        Some(Response::Error(DeviceError::EmptySlot(0)))
+    );
+    // Other devices' commands are ignored
+    assert_eq!(
+        host.send_to(
+            DEVICE_ID + 1,
+            Command::UploadCue {
+                slot: 0,
+                cue: Cue::rainbow(),
+            }
+        ),
+        None
+    );
+    assert_eq!(host.device.slot(0), None);
+}
+
+#[test]
+fn streaming_input() {
+    let mut host = Host::new();
+    let commands: [Command<16>; 3] = [
+        Command::UploadCue {
+            slot: 0,
+            cue: Cue::starry_sky(),
+        },
+        Command::LaunchSlot { slot: 0 },
+        Command::Identify,
+    ];
+    let mut stream = Vec::new();
+    for command in commands.iter() {
+        let mut message = [0; MAX_MESSAGE_LEN];
+        let len = command.encode_sysex(DEVICE_ID, &mut message).unwrap();
+        for byte in &message[..len] {
+            stream.push(*byte);
+            // Timing clock, like a DAW would send
+            stream.push(0xF8);
+        }
+    }
+
+    // Deliver the stream in uneven pieces
+    let mut responses = Vec::new();
+    for piece in stream.chunks(7) {
+        responses.extend(host.send_bytes(piece));
+    }
+    assert_eq!(responses.len(), 3);
+    assert_eq!(responses[0], Ok((DEVICE_ID, Response::Ack)));
+    assert_eq!(responses[1], Ok((DEVICE_ID, Response::Ack)));
+    assert!(matches!(
+        responses[2],
+        Ok((DEVICE_ID, Response::Identity(_)))
+    ));
+    assert_eq!(host.device.slot(0), Some(&Cue::starry_sky()));
+}